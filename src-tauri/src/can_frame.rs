@@ -0,0 +1,714 @@
+//! 校验版的固定协议帧包装类型
+//!
+//! `parse_received_can_message` 过去是"读 `&[u8]`，返回 `Option<(String, String, String)>`"
+//! 的弱类型风格：调用方拿到 `None` 分不清到底是"帧太短"还是"帧头不对"还是"校验和不对"，
+//! 而校验和不对时甚至不会失败，只是打一条日志继续往下解析。这里仿照 smoltcp 的
+//! Packet 包装惯例拆成两层：
+//! - [`Packet`] 只负责按固定协议的字节布局定位字段，`new_checked` 会把长度、帧头、
+//!   DLC、校验和一次性验完，验过的 `Packet` 之后的字段访问不会再失败；
+//! - [`CanFrame`] 是校验通过后的强类型表示，[`CanFrame::parse`]/[`CanFrame::emit`]
+//!   让编码和解码共用同一套字段布局，不会出现两边各写一份、字段顺序慢慢漂移的问题。
+//!
+//! `can_protocol.rs` 里 `parse_received_can_message` 现在就是这一层之上的一个
+//! 瘦包装；失败原因由 `parse_received_can_message_checked` 把 [`Error`] 翻译成
+//! `CanParseError` 暴露给调用方。
+
+use std::fmt;
+
+use crate::can_protocol::{dlc_to_len, len_to_dlc};
+
+/// 固定协议各字段相对帧起始的字节偏移量
+mod field {
+    pub const TYPE: usize = 2;
+    pub const FRAME_TYPE: usize = 3;
+    pub const FRAME_MODE: usize = 4;
+    pub const CAN_ID_START: usize = 5;
+    pub const DLC: usize = 9;
+    pub const DATA_START: usize = 10;
+    /// 不含数据负载时，头部 + ID + DLC + 保留字节 + 校验和 的最短长度（经典 CAN，DLC=0）
+    pub const MIN_LEN: usize = DATA_START + 2;
+}
+
+/// `Packet::new_checked`/`CanFrame::emit` 能失败的具体原因，替代原来只返回
+/// `None`/打日志的做法，让调用方能按失败类别分别处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// 缓冲区长度不够放下声明的帧；`expected` 是按已知字段算出来的最短长度
+    TooShort { expected: usize, got: usize },
+    /// 前两个字节不是 `0xAA 0x55`
+    InvalidHeader,
+    /// DLC 字节不在 [`dlc_to_len`] 能识别的取值范围内（用在解析收到的帧上），或者
+    /// [`CanFrameBuilder::build`]/[`CanFrame::to_variable_packet`] 发现数据长度超出
+    /// 当前帧类型允许的范围（用在构造/编码上）
+    InvalidDlc(u8),
+    /// 收到的校验和字节和按内容重新算出来的不一致
+    ChecksumMismatch { expected: u8, got: u8 },
+    /// [`CanFrameBuilder::build`] 里 CAN ID 超出了标准帧 11 位 / 扩展帧 29 位的范围
+    IdOutOfRange { id: u32, max: u32 },
+    /// [`CanFrame::to_variable_packet`] 在 `header` 被设置时返回这个——变长协议的
+    /// 控制字节 8 位全部已经有含义（bit7-6 固定 / bit5 扩展帧 / bit4 远程帧 /
+    /// bit3-0 数据长度），没有空位能塞 [`FrameHeader`]，只有固定协议的帧才有真正
+    /// 闲置的类型字节/保留字节可以复用
+    HeaderNotSupported,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TooShort { expected, got } => write!(
+                f,
+                "CAN frame too short: expected at least {} bytes, got {}",
+                expected, got
+            ),
+            Error::InvalidHeader => write!(f, "invalid CAN frame header, expected 0xAA 0x55"),
+            Error::InvalidDlc(dlc) => write!(f, "invalid DLC byte: {}", dlc),
+            Error::ChecksumMismatch { expected, got } => write!(
+                f,
+                "CAN frame checksum mismatch: expected 0x{:02X}, got 0x{:02X}",
+                expected, got
+            ),
+            Error::IdOutOfRange { id, max } => {
+                write!(f, "CAN ID 0x{:X} exceeds the maximum of 0x{:X} for this frame type", id, max)
+            }
+            Error::HeaderNotSupported => write!(
+                f,
+                "FrameHeader is only supported by the fixed-protocol frame, not the variable-protocol packet"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// 固定协议里类型字节的两个取值：没有 [`FrameHeader`] 时和历史行为完全一致，
+/// 带 `FrameHeader` 时换成另一个值，接收端靠它判断帧尾是不是多了 4 字节的头部
+const TYPE_NORMAL: u8 = 0x01;
+const TYPE_WITH_HEADER: u8 = 0x02;
+/// [`FrameHeader`] 序列化后固定占用的字节数（`seq` 2 字节 + `source_tag` 1 字节 +
+/// `flags` 1 字节）
+const FRAME_HEADER_LEN: usize = 4;
+
+/// 挂在发送包上的可选 out-of-band 元数据：不属于 CAN 总线本身的字段，纯粹是这个
+/// app 内部多路复用多个数据源时用来标记"这包是谁发的、第几个"；复用固定协议里
+/// 本来就没有实际校验、一直固定写 `0x01`/`0x00` 的类型字节和保留字节——类型字节
+/// 换成 [`TYPE_WITH_HEADER`] 表示"帧尾多了这 4 个字节"，帧长度和校验和的计算也
+/// 相应往后挪 [`FRAME_HEADER_LEN`] 字节。不设置时（`CanFrame::header` 为 `None`）
+/// 编码出来的字节和历史行为完全一样，完全向后兼容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub seq: u16,
+    pub source_tag: u8,
+    pub flags: u8,
+}
+
+/// 对一段字节按固定协议布局做访问；`new_unchecked` 不做任何校验，字段越界时会 panic
+/// （和 smoltcp 的惯例一致），只应该喂给已知合法的缓冲区（比如刚用 `emit` 写过的）。
+/// `new_checked` 在构造时就把长度/帧头/DLC/校验和都验一遍，换来的 `Packet` 后续的
+/// 字段访问都是安全的。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet<T: AsRef<[u8]>> {
+    buffer: T,
+}
+
+impl<T: AsRef<[u8]>> Packet<T> {
+    pub fn new_unchecked(buffer: T) -> Packet<T> {
+        Packet { buffer }
+    }
+
+    pub fn new_checked(buffer: T) -> Result<Packet<T>> {
+        let packet = Packet::new_unchecked(buffer);
+
+        let len = packet.buffer.as_ref().len();
+        if len < field::MIN_LEN {
+            return Err(Error::TooShort {
+                expected: field::MIN_LEN,
+                got: len,
+            });
+        }
+
+        let bytes = packet.buffer.as_ref();
+        if bytes[0] != 0xAA || bytes[1] != 0x55 {
+            return Err(Error::InvalidHeader);
+        }
+
+        let dlc = packet.dlc();
+        let data_len = dlc_to_len(dlc).ok_or(Error::InvalidDlc(dlc))?;
+
+        let checksum_index = field::DATA_START + data_len + packet.header_len() + 1;
+        if len < checksum_index + 1 {
+            return Err(Error::TooShort {
+                expected: checksum_index + 1,
+                got: len,
+            });
+        }
+
+        let expected = packet.checksum_of(checksum_index);
+        let got = packet.buffer.as_ref()[checksum_index];
+        if expected != got {
+            return Err(Error::ChecksumMismatch { expected, got });
+        }
+
+        Ok(packet)
+    }
+
+    /// 从 `field::TYPE` 到（不含）`checksum_index` 这一段按字节求和取低 8 位，
+    /// 和 `can_protocol.rs` 里 `create_can_send_packet_fixed`/`_fixed_fd` 用的算法保持一致
+    /// （变长协议的包不带校验和）
+    fn checksum_of(&self, checksum_index: usize) -> u8 {
+        self.buffer.as_ref()[field::TYPE..checksum_index]
+            .iter()
+            .map(|&b| b as u32)
+            .sum::<u32>() as u8
+    }
+
+    pub fn type_byte(&self) -> u8 {
+        self.buffer.as_ref()[field::TYPE]
+    }
+
+    /// 类型字节是否标记了"帧尾带 [`FrameHeader`]"
+    fn has_frame_header(&self) -> bool {
+        self.type_byte() == TYPE_WITH_HEADER
+    }
+
+    /// 帧尾 [`FrameHeader`] 占用的字节数；没有时为 0，用来算校验和/帧总长
+    fn header_len(&self) -> usize {
+        if self.has_frame_header() {
+            FRAME_HEADER_LEN
+        } else {
+            0
+        }
+    }
+
+    /// 解析出帧尾挂着的 [`FrameHeader`]；类型字节没有标记 header 时返回 `None`。
+    /// 只应该在 `new_checked` 验过的 `Packet` 上调用
+    pub fn frame_header(&self) -> Option<FrameHeader> {
+        if !self.has_frame_header() {
+            return None;
+        }
+        let start = field::DATA_START + self.data_len();
+        let b = self.buffer.as_ref();
+        Some(FrameHeader {
+            seq: u16::from_le_bytes([b[start], b[start + 1]]),
+            source_tag: b[start + 2],
+            flags: b[start + 3],
+        })
+    }
+
+    pub fn frame_type_byte(&self) -> u8 {
+        self.buffer.as_ref()[field::FRAME_TYPE]
+    }
+
+    pub fn frame_mode_byte(&self) -> u8 {
+        self.buffer.as_ref()[field::FRAME_MODE]
+    }
+
+    pub fn can_id_raw(&self) -> u32 {
+        let b = self.buffer.as_ref();
+        (b[field::CAN_ID_START] as u32)
+            | ((b[field::CAN_ID_START + 1] as u32) << 8)
+            | ((b[field::CAN_ID_START + 2] as u32) << 16)
+            | ((b[field::CAN_ID_START + 3] as u32) << 24)
+    }
+
+    pub fn dlc(&self) -> u8 {
+        self.buffer.as_ref()[field::DLC]
+    }
+
+    /// DLC 解码后的数据字节数；只应该在 `new_checked` 验过的 `Packet` 上调用，
+    /// 未校验的 `Packet` 上遇到非法 DLC 会回退成 0
+    pub fn data_len(&self) -> usize {
+        dlc_to_len(self.dlc()).unwrap_or(0)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        let len = self.data_len();
+        &self.buffer.as_ref()[field::DATA_START..field::DATA_START + len]
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
+    pub fn set_frame_type_byte(&mut self, value: u8) {
+        self.buffer.as_mut()[field::FRAME_TYPE] = value;
+    }
+
+    pub fn set_frame_mode_byte(&mut self, value: u8) {
+        self.buffer.as_mut()[field::FRAME_MODE] = value;
+    }
+
+    pub fn set_can_id_raw(&mut self, id: u32) {
+        let bytes = id.to_le_bytes();
+        self.buffer.as_mut()[field::CAN_ID_START..field::CAN_ID_START + 4]
+            .copy_from_slice(&bytes);
+    }
+
+    pub fn set_dlc(&mut self, dlc: u8) {
+        self.buffer.as_mut()[field::DLC] = dlc;
+    }
+
+    /// 按当前 DLC 取出可写的数据区；缓冲区长度必须已经按 [`CanFrame::buffer_len`]
+    /// 分配好，否则返回长度不足的错误
+    pub fn data_mut(&mut self) -> Result<&mut [u8]> {
+        let len = self.data_len();
+        let end = field::DATA_START + len;
+        if self.buffer.as_mut().len() < end {
+            return Err(Error::TooShort {
+                expected: end,
+                got: self.buffer.as_mut().len(),
+            });
+        }
+        Ok(&mut self.buffer.as_mut()[field::DATA_START..end])
+    }
+
+    /// 在数据区之后写入 [`FrameHeader`]；调用方必须先把类型字节设成
+    /// [`TYPE_WITH_HEADER`]（`CanFrame::emit` 已经这样做了），否则这里写的位置
+    /// 和 `frame_header()` 读的位置对不上
+    fn set_frame_header(&mut self, header: &FrameHeader) -> Result<()> {
+        let start = field::DATA_START + self.data_len();
+        let end = start + FRAME_HEADER_LEN;
+        if self.buffer.as_mut().len() < end {
+            return Err(Error::TooShort {
+                expected: end,
+                got: self.buffer.as_mut().len(),
+            });
+        }
+        let seq_bytes = header.seq.to_le_bytes();
+        let b = self.buffer.as_mut();
+        b[start] = seq_bytes[0];
+        b[start + 1] = seq_bytes[1];
+        b[start + 2] = header.source_tag;
+        b[start + 3] = header.flags;
+        Ok(())
+    }
+
+    /// 写保留字节（固定 `0x00`）和校验和，必须在其它字段（包括可选的
+    /// [`FrameHeader`]）都写完之后最后调用
+    pub fn fill_reserved_and_checksum(&mut self) -> Result<()> {
+        let data_len = self.data_len();
+        let checksum_index = field::DATA_START + data_len + self.header_len() + 1;
+        if self.buffer.as_mut().len() < checksum_index + 1 {
+            return Err(Error::TooShort {
+                expected: checksum_index + 1,
+                got: self.buffer.as_mut().len(),
+            });
+        }
+        self.buffer.as_mut()[checksum_index - 1] = 0x00; // reserved byte
+        let checksum = self.checksum_of(checksum_index);
+        self.buffer.as_mut()[checksum_index] = checksum;
+        Ok(())
+    }
+}
+
+/// 校验通过后的固定协议 CAN 帧，[`CanFrame::parse`]/[`CanFrame::emit`] 在这个
+/// 表示和 [`Packet`] 之间转换，编码解码共用同一套字段布局
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanFrame {
+    pub id: u32,
+    pub extended: bool,
+    /// 这一帧是否为 CAN FD 帧（帧模式字节 bit1 / FDF）
+    pub fd: bool,
+    /// 是否为远程帧；固定协议的字节布局里没有单独的 RTR 位，`parse` 出来的帧
+    /// 这里始终是 `false`，`emit` 也不写它；变长协议有 RTR 位，
+    /// [`CanFrame::to_variable_packet`] 会按这个字段编码
+    pub remote: bool,
+    pub data: Vec<u8>,
+    /// 多路复用场景下挂在这一帧上的可选元数据，参见 [`FrameHeader`]；`None` 时
+    /// `emit` 编码出来的字节和加这个字段之前完全一样
+    pub header: Option<FrameHeader>,
+}
+
+impl CanFrame {
+    /// 按当前 `data` 长度（以及是否带 [`FrameHeader`]）算出 `emit` 需要的缓冲区
+    /// 总长度；`data.len()` 不是合法 DLC 长度（见 [`dlc_to_len`]/[`len_to_dlc`]）
+    /// 时返回 `None`
+    pub fn buffer_len(&self) -> Option<usize> {
+        len_to_dlc(self.data.len())?;
+        let header_len = if self.header.is_some() { FRAME_HEADER_LEN } else { 0 };
+        Some(field::DATA_START + self.data.len() + header_len + 2)
+    }
+
+    /// 从已经用 `new_checked` 验过的 `Packet` 里取出字段；`Packet` 的校验已经
+    /// 保证了长度/帧头/DLC/校验和都合法，这一步不会再失败
+    pub fn parse<T: AsRef<[u8]>>(packet: &Packet<T>) -> CanFrame {
+        CanFrame {
+            id: packet.can_id_raw(),
+            extended: packet.frame_type_byte() == 0x02,
+            fd: packet.frame_mode_byte() & 0x02 != 0,
+            remote: false,
+            data: packet.data().to_vec(),
+            header: packet.frame_header(),
+        }
+    }
+
+    /// 把这一帧写进 `packet`；`packet` 底层缓冲区长度至少要有 [`CanFrame::buffer_len`]，
+    /// 不够时返回 `Error::TooShort`（连数据区之前的固定字段都放不下时，和
+    /// `new_unchecked` 的字段访问一样直接 panic）；多出来的尾部字节不会被写
+    pub fn emit<T: AsRef<[u8]> + AsMut<[u8]>>(&self, packet: &mut Packet<T>) -> Result<()> {
+        let dlc = len_to_dlc(self.data.len()).ok_or_else(|| Error::InvalidDlc(self.data.len() as u8))?;
+
+        packet.buffer.as_mut()[0] = 0xAA;
+        packet.buffer.as_mut()[1] = 0x55;
+        packet.buffer.as_mut()[field::TYPE] = if self.header.is_some() { TYPE_WITH_HEADER } else { TYPE_NORMAL };
+        packet.set_frame_type_byte(if self.extended { 0x02 } else { 0x01 });
+
+        let mut frame_mode_byte = 0x01;
+        if self.fd {
+            frame_mode_byte |= 0x02;
+        }
+        packet.set_frame_mode_byte(frame_mode_byte);
+
+        packet.set_can_id_raw(self.id);
+        packet.set_dlc(dlc);
+        packet.data_mut()?.copy_from_slice(&self.data);
+        if let Some(header) = &self.header {
+            packet.set_frame_header(header)?;
+        }
+        packet.fill_reserved_and_checksum()?;
+
+        Ok(())
+    }
+
+    /// 编码成"变长协议"的发送包：`0xAA` 起始 + 控制字节（bit7-6=11 固定，bit5=扩展帧，
+    /// bit4=远程帧，bit3-0=数据长度）+ CAN ID（标准帧2字节/扩展帧4字节，小端序）+
+    /// 数据（远程帧无数据负载）+ `0x55` 结束，和 `can_protocol.rs` 的
+    /// `create_can_send_packet_variable` 是同一套布局，这里把编码逻辑收拢到一处，
+    /// 不用每个调用方各自拼控制字节
+    ///
+    /// 这种变长协议里 DLC 只有 0-8 一档，不支持 CAN FD 的扩展长度
+    pub fn to_variable_packet(&self) -> Result<Vec<u8>> {
+        if self.header.is_some() {
+            return Err(Error::HeaderNotSupported);
+        }
+
+        if self.data.len() > 8 {
+            return Err(Error::InvalidDlc(self.data.len() as u8));
+        }
+
+        let data_len = if self.remote { 0 } else { self.data.len() };
+
+        let mut control_byte = if self.extended { 0xE0 } else { 0xC0 };
+        if self.remote {
+            control_byte |= 0x10;
+        }
+        control_byte |= data_len as u8;
+
+        let mut packet = vec![0xAA, control_byte];
+
+        if self.extended {
+            packet.extend_from_slice(&self.id.to_le_bytes());
+        } else {
+            let id_u16 = (self.id & 0xFFFF) as u16;
+            packet.extend_from_slice(&id_u16.to_le_bytes());
+        }
+
+        if !self.remote {
+            packet.extend_from_slice(&self.data);
+        }
+
+        packet.push(0x55);
+        Ok(packet)
+    }
+
+    /// 编码成"固定协议"的发送包（包长 12 + 数据长度，8 字节经典 CAN 数据时正好 20 字节；
+    /// 数据不足 8 字节时不补齐），复用
+    /// [`CanFrame::buffer_len`]/[`CanFrame::emit`] 同一套字段布局，不用像
+    /// `can_protocol.rs` 里 `create_can_send_packet_fixed` 那样手工拼字节；
+    /// `isotp.rs` 按这个接口逐帧发送分段后的 ISO-TP 帧
+    pub fn to_fixed_packet(&self) -> Result<Vec<u8>> {
+        let len = self.buffer_len().ok_or_else(|| Error::InvalidDlc(self.data.len() as u8))?;
+        let mut buf = vec![0u8; len];
+        let mut packet = Packet::new_unchecked(buf.as_mut_slice());
+        self.emit(&mut packet)?;
+        Ok(buf)
+    }
+
+    /// 从收到的固定协议字节里解析出一个 [`CanFrame`]；和
+    /// `can_protocol::parse_received_can_message_checked` 共用同一份 `Packet::new_checked`
+    /// 校验逻辑，只是不压平成字符串字段
+    pub fn from_rx_message(
+        data: &[u8],
+    ) -> std::result::Result<CanFrame, crate::can_protocol::CanParseError> {
+        crate::can_protocol::parse_can_frame_checked(data)
+    }
+}
+
+/// [`CanFrame`] 的构造器：按标准帧 11 位 / 扩展帧 29 位校验 ID 范围，按是否 FD
+/// 校验数据长度，避免调用方拼出一个字段之间自相矛盾的 `CanFrame`（比如远程帧却带
+/// 着数据，或者 ID 超出当前帧类型允许的范围）
+#[derive(Debug, Clone, Default)]
+pub struct CanFrameBuilder {
+    id: u32,
+    extended: bool,
+    fd: bool,
+    remote: bool,
+    data: Vec<u8>,
+    header: Option<FrameHeader>,
+}
+
+impl CanFrameBuilder {
+    pub fn new(id: u32) -> Self {
+        CanFrameBuilder { id, ..Default::default() }
+    }
+
+    pub fn extended(mut self, extended: bool) -> Self {
+        self.extended = extended;
+        self
+    }
+
+    pub fn fd(mut self, fd: bool) -> Self {
+        self.fd = fd;
+        self
+    }
+
+    pub fn remote(mut self, remote: bool) -> Self {
+        self.remote = remote;
+        self
+    }
+
+    pub fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// 给这一帧挂上 [`FrameHeader`]；只有固定协议支持，`build` 出来的 `CanFrame`
+    /// 调 [`CanFrame::to_variable_packet`] 会返回 `Error::HeaderNotSupported`
+    pub fn header(mut self, header: FrameHeader) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn build(self) -> Result<CanFrame> {
+        let max_id = if self.extended { 0x1FFF_FFFF } else { 0x7FF };
+        if self.id > max_id {
+            return Err(Error::IdOutOfRange { id: self.id, max: max_id });
+        }
+
+        if self.remote && !self.data.is_empty() {
+            return Err(Error::InvalidDlc(self.data.len() as u8));
+        }
+
+        if self.fd {
+            len_to_dlc(self.data.len()).ok_or_else(|| Error::InvalidDlc(self.data.len() as u8))?;
+        } else if self.data.len() > 8 {
+            return Err(Error::InvalidDlc(self.data.len() as u8));
+        }
+
+        Ok(CanFrame {
+            id: self.id,
+            extended: self.extended,
+            fd: self.fd,
+            remote: self.remote,
+            data: self.data,
+            header: self.header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classic_frame_bytes() -> Vec<u8> {
+        // AA 55 01 02 01 | D2 D2 C4 18 | 08 | 8 data bytes | 00 | checksum
+        // frame_type=0x02 (extended): the ID 0x18C4D2D2 is 29-bit and can't be a standard frame
+        let mut packet = vec![0xAA, 0x55, 0x01, 0x02, 0x01, 0xD2, 0xD2, 0xC4, 0x18, 0x08];
+        packet.extend_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+        packet.push(0x00);
+        let checksum: u8 = packet[2..].iter().map(|&b| b as u32).sum::<u32>() as u8;
+        packet.push(checksum);
+        packet
+    }
+
+    #[test]
+    fn new_checked_accepts_valid_classic_frame() {
+        let bytes = classic_frame_bytes();
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        assert_eq!(packet.can_id_raw(), 0x18C4D2D2);
+        assert_eq!(packet.data(), &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+    }
+
+    #[test]
+    fn new_checked_rejects_bad_header() {
+        let mut bytes = classic_frame_bytes();
+        bytes[0] = 0x00;
+        assert_eq!(Packet::new_checked(bytes.as_slice()), Err(Error::InvalidHeader));
+    }
+
+    #[test]
+    fn new_checked_rejects_too_short() {
+        let bytes = vec![0xAA, 0x55, 0x01];
+        assert_eq!(
+            Packet::new_checked(bytes.as_slice()),
+            Err(Error::TooShort {
+                expected: field::MIN_LEN,
+                got: 3
+            })
+        );
+    }
+
+    #[test]
+    fn new_checked_rejects_invalid_dlc() {
+        let mut bytes = classic_frame_bytes();
+        bytes[field::DLC] = 0xFF;
+        assert_eq!(Packet::new_checked(bytes.as_slice()), Err(Error::InvalidDlc(0xFF)));
+    }
+
+    #[test]
+    fn new_checked_rejects_checksum_mismatch() {
+        let mut bytes = classic_frame_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        assert!(matches!(
+            Packet::new_checked(bytes.as_slice()),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn can_frame_parse_round_trips_through_emit() {
+        let bytes = classic_frame_bytes();
+        let packet = Packet::new_checked(bytes.as_slice()).unwrap();
+        let frame = CanFrame::parse(&packet);
+
+        assert_eq!(frame.id, 0x18C4D2D2);
+        assert!(frame.extended);
+        assert!(!frame.fd);
+
+        let mut out = vec![0u8; frame.buffer_len().unwrap()];
+        let mut out_packet = Packet::new_unchecked(out.as_mut_slice());
+        frame.emit(&mut out_packet).unwrap();
+
+        assert_eq!(out, bytes);
+    }
+
+    #[test]
+    fn can_frame_emit_rejects_oversized_data() {
+        let frame = CanFrame {
+            id: 0x123,
+            extended: false,
+            fd: false,
+            remote: false,
+            data: vec![0u8; 9], // 9 is not a valid classic or FD length
+            header: None,
+        };
+        assert!(frame.buffer_len().is_none());
+
+        let mut out = vec![0u8; 32];
+        let mut packet = Packet::new_unchecked(out.as_mut_slice());
+        assert_eq!(frame.emit(&mut packet), Err(Error::InvalidDlc(9)));
+    }
+
+    #[test]
+    fn builder_rejects_id_out_of_range_for_standard_frame() {
+        let err = CanFrameBuilder::new(0x800).build().unwrap_err();
+        assert_eq!(err, Error::IdOutOfRange { id: 0x800, max: 0x7FF });
+    }
+
+    #[test]
+    fn builder_accepts_extended_id_that_would_overflow_standard_frame() {
+        let frame = CanFrameBuilder::new(0x800).extended(true).data(vec![0x01]).build().unwrap();
+        assert_eq!(frame.id, 0x800);
+        assert!(frame.extended);
+    }
+
+    #[test]
+    fn builder_rejects_remote_frame_with_data() {
+        let err = CanFrameBuilder::new(0x123).remote(true).data(vec![0x01]).build().unwrap_err();
+        assert_eq!(err, Error::InvalidDlc(1));
+    }
+
+    #[test]
+    fn builder_rejects_fd_with_non_table_length() {
+        let err = CanFrameBuilder::new(0x123).fd(true).data(vec![0u8; 9]).build().unwrap_err();
+        assert_eq!(err, Error::InvalidDlc(9));
+    }
+
+    #[test]
+    fn to_variable_packet_encodes_standard_data_frame() {
+        let frame = CanFrameBuilder::new(0x123).data(vec![0x11, 0x22]).build().unwrap();
+        assert_eq!(
+            frame.to_variable_packet().unwrap(),
+            vec![0xAA, 0xC2, 0x23, 0x01, 0x11, 0x22, 0x55]
+        );
+    }
+
+    #[test]
+    fn to_variable_packet_encodes_extended_remote_frame_without_data() {
+        let frame = CanFrameBuilder::new(0x18C4D2D0).extended(true).remote(true).build().unwrap();
+        assert_eq!(
+            frame.to_variable_packet().unwrap(),
+            vec![0xAA, 0xF0, 0xD0, 0xD2, 0xC4, 0x18, 0x55]
+        );
+    }
+
+    #[test]
+    fn from_rx_message_matches_checked_parser() {
+        let bytes = classic_frame_bytes();
+        let frame = CanFrame::from_rx_message(&bytes).unwrap();
+        assert_eq!(frame.id, 0x18C4D2D2);
+        assert_eq!(frame.data, vec![0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+    }
+
+    #[test]
+    fn frame_without_header_emits_byte_identical_to_pre_header_behavior() {
+        // 没挂 FrameHeader 时，类型字节必须还是 0x01，帧长/校验和都和加这个
+        // 字段之前完全一样，不然所有不知道 FrameHeader 存在的旧接收端都会读错
+        let frame = CanFrameBuilder::new(0x123).data(vec![0x11, 0x22]).build().unwrap();
+        assert!(frame.header.is_none());
+
+        let mut out = vec![0u8; frame.buffer_len().unwrap()];
+        let mut packet = Packet::new_unchecked(out.as_mut_slice());
+        frame.emit(&mut packet).unwrap();
+
+        assert_eq!(packet.type_byte(), 0x01);
+        assert_eq!(out.len(), field::MIN_LEN + 2);
+        assert!(Packet::new_checked(out.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn frame_with_header_round_trips_through_emit_and_parse() {
+        let header = FrameHeader { seq: 0x4241, source_tag: 0x05, flags: 0x01 };
+        let frame = CanFrameBuilder::new(0x123)
+            .data(vec![0x11, 0x22])
+            .header(header)
+            .build()
+            .unwrap();
+
+        let mut out = vec![0u8; frame.buffer_len().unwrap()];
+        let mut packet = Packet::new_unchecked(out.as_mut_slice());
+        frame.emit(&mut packet).unwrap();
+
+        assert_eq!(packet.type_byte(), 0x02);
+        // 比不带 header 的同一帧多 4 个字节
+        assert_eq!(out.len(), field::MIN_LEN + 2 + FRAME_HEADER_LEN);
+
+        let checked = Packet::new_checked(out.as_slice()).unwrap();
+        assert_eq!(checked.frame_header(), Some(header));
+
+        let parsed = CanFrame::parse(&checked);
+        assert_eq!(parsed.header, Some(header));
+        assert_eq!(parsed.data, vec![0x11, 0x22]);
+    }
+
+    #[test]
+    fn to_fixed_packet_matches_manually_built_classic_frame() {
+        let frame = CanFrameBuilder::new(0x18C4D2D2)
+            .extended(true)
+            .data(vec![0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88])
+            .build()
+            .unwrap();
+        assert_eq!(frame.to_fixed_packet().unwrap(), classic_frame_bytes());
+    }
+
+    #[test]
+    fn to_variable_packet_rejects_frame_with_header() {
+        let header = FrameHeader { seq: 1, source_tag: 0, flags: 0 };
+        let frame = CanFrameBuilder::new(0x123).data(vec![0x11]).header(header).build().unwrap();
+        assert_eq!(frame.to_variable_packet().unwrap_err(), Error::HeaderNotSupported);
+    }
+}
@@ -4,8 +4,99 @@
 use anyhow::{Result, anyhow};
 use log::info;
 
+use crate::can_frame::{CanFrame, FrameHeader, Packet};
+use crate::j1939::J1939Id;
 use crate::SerialConfig;
 
+/// CAN FD 的 DLC -> 字节数映射；DLC 0-8 直接对应 0-8 字节，9 往上是非线性的几档
+///
+/// `pub(crate)` 给 `can_frame.rs` 的 `Packet`/`CanFrame` 复用，两边共享同一套映射表，
+/// 不会出现分别维护、悄悄漂移的情况
+pub(crate) fn dlc_to_len(dlc: u8) -> Option<usize> {
+    match dlc {
+        0..=8 => Some(dlc as usize),
+        9 => Some(12),
+        10 => Some(16),
+        11 => Some(20),
+        12 => Some(24),
+        13 => Some(32),
+        14 => Some(48),
+        15 => Some(64),
+        _ => None,
+    }
+}
+
+/// [`dlc_to_len`] 的反函数；只接受表里出现过的那几个精确长度
+pub(crate) fn len_to_dlc(len: usize) -> Option<u8> {
+    match len {
+        0..=8 => Some(len as u8),
+        12 => Some(9),
+        16 => Some(10),
+        20 => Some(11),
+        24 => Some(12),
+        32 => Some(13),
+        48 => Some(14),
+        64 => Some(15),
+        _ => None,
+    }
+}
+
+/// 解析十六进制数据字符串（空格分隔或连续两位一字节），不做长度上限校验，
+/// 交给调用方按经典 CAN（8 字节）、CAN FD（见 [`len_to_dlc`]）或者 ISO-TP 分段
+/// （见 `isotp.rs`）各自的规则自行限制；`pub(crate)` 是因为 `isotp.rs` 解析
+/// `send_isotp` 命令传进来的负载时也要用同一份解析逻辑
+pub(crate) fn parse_hex_data_bytes(data: &str) -> Result<Vec<u8>> {
+    if data.contains(' ') {
+        data.split_whitespace()
+            .map(|s| u8::from_str_radix(s, 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| anyhow!("Invalid space-separated hex data"))
+    } else {
+        let len = data.len();
+        if len % 2 != 0 {
+            return Err(anyhow!("Data string is not space-separated and has an odd length, expected two hex digits per byte."));
+        }
+
+        data.as_bytes()
+            .chunks(2)
+            .map(|chunk| {
+                let hex_str = std::str::from_utf8(chunk)
+                    .map_err(|_| anyhow!("Failed to convert byte chunk to string"))?;
+                u8::from_str_radix(hex_str, 16)
+                    .map_err(|_| anyhow!("Invalid continuous hex data: {}", hex_str))
+            })
+            .collect::<Result<Vec<u8>, _>>()
+    }
+}
+
+/// 校验/解析一个 CAN ID 字符串（可选 "0x"/"0X" 前缀），按标准帧 11 位 / 扩展帧 29 位
+/// 校验范围；空字符串回退到默认 ID，和 `create_can_send_packet_fixed`/`_variable`
+/// 保持一致的历史行为
+fn parse_and_validate_can_id(id: &str, is_extended: bool) -> Result<u32> {
+    let id_hex_part = id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")).unwrap_or(id);
+    let can_id = if id_hex_part.is_empty() {
+        0x18C4D2D0
+    } else {
+        u32::from_str_radix(id_hex_part, 16).map_err(|_| anyhow!("Invalid CAN ID format: \"{}\"", id))?
+    };
+
+    if !is_extended {
+        if can_id > 0x7FF {
+            return Err(anyhow!(
+                "Invalid CAN ID for standard frame: 0x{:X}. Standard frame CAN ID must be <= 0x7FF (11-bit)",
+                can_id
+            ));
+        }
+    } else if can_id > 0x1FFFFFFF {
+        return Err(anyhow!(
+            "Invalid CAN ID for extended frame: 0x{:X}. Extended frame CAN ID must be <= 0x1FFFFFFF (29-bit)",
+            can_id
+        ));
+    }
+
+    Ok(can_id)
+}
+
 /// 创建 CAN 配置数据包
 /// 
 /// 根据配置参数生成 CAN 配置命令数据包
@@ -56,6 +147,19 @@ pub fn create_can_config_packet(config: &SerialConfig) -> Vec<u8> {
     packet.push(0x00);
     packet.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
 
+    // CAN FD 使能 + 数据段波特率；旧款只支持 CAN 2.0 的适配器会忽略这两个尾部字节
+    packet.push(if config.can_fd_enabled { 0x01 } else { 0x00 });
+    let data_baud_config = match config.data_baud_rate {
+        500_000 => 0x05,
+        1_000_000 => 0x04,
+        2_000_000 => 0x03,
+        4_000_000 => 0x02,
+        5_000_000 => 0x01,
+        8_000_000 => 0x00,
+        _ => 0x03, // Default 2Mbps data phase
+    };
+    packet.push(data_baud_config);
+
     // Calculate checksum
     let checksum: u8 = packet[2..].iter().map(|&b| b as u32).sum::<u32>() as u8 & 0xFF;
     packet.push(checksum);
@@ -167,6 +271,78 @@ pub fn create_can_send_packet_fixed(id: &str, data: &str, frame_type: &str) -> R
     Ok(packet)
 }
 
+/// 按 J1939 字段（优先级 + PGN + 源地址）构造一个固定20字节协议的发送包，
+/// 调用方不用再自己手工拼 29 位扩展 ID
+///
+/// 总是按扩展帧发送，因为 J1939 本身就是基于 29 位扩展 ID 定义的
+pub fn create_can_send_packet_fixed_j1939(
+    priority: u8,
+    pgn: u32,
+    source_address: u8,
+    data: &str,
+) -> Result<Vec<u8>> {
+    let j1939 = J1939Id {
+        priority,
+        pgn,
+        source_address,
+        destination_address: None,
+    };
+    let id = format!("0x{:08X}", j1939.to_raw());
+    create_can_send_packet_fixed(&id, data, "extended")
+}
+
+/// 创建 CAN FD 发送数据包（固定协议），支持 12/16/20/24/32/48/64 字节负载
+///
+/// 负载长度必须精确匹配 [`len_to_dlc`] 里的某一档，写进原来"数据长度"所在的
+/// 字节位置（对经典帧来说这个字节本来就等于字节数，因为 DLC 0-8 和字节数 0-8 相同，
+/// 所以 `parse_received_can_message` 不需要额外区分就能按同一套逻辑解码两种帧）。
+/// `brs`（Bit Rate Switch）/`esi`（Error State Indicator）写进帧模式字节的 bit2/bit3，
+/// bit1 置 1 标记这是一帧 FD 帧，bit0 沿用旧协议里恒为 1 的帧模式常量位。
+pub fn create_can_send_packet_fixed_fd(
+    id: &str,
+    data: &str,
+    frame_type: &str,
+    brs: bool,
+    esi: bool,
+) -> Result<Vec<u8>> {
+    let data_bytes = parse_hex_data_bytes(data)?;
+    let dlc = len_to_dlc(data_bytes.len()).ok_or_else(|| {
+        anyhow!(
+            "CAN FD payload length {} is not a valid size (0-8, 12, 16, 20, 24, 32, 48, 64)",
+            data_bytes.len()
+        )
+    })?;
+
+    let is_extended = frame_type == "extended";
+    let can_id = parse_and_validate_can_id(id, is_extended)?;
+
+    let frame_type_byte = if is_extended { 0x02 } else { 0x01 };
+    // bit0=1 (兼容旧协议的帧模式常量位), bit1=FDF, bit2=BRS, bit3=ESI
+    let mut frame_mode_byte = 0x01 | 0x02;
+    if brs {
+        frame_mode_byte |= 0x04;
+    }
+    if esi {
+        frame_mode_byte |= 0x08;
+    }
+
+    let mut packet = vec![0xAA, 0x55, 0x01, frame_type_byte, frame_mode_byte];
+    packet.extend_from_slice(&can_id.to_le_bytes());
+    packet.push(dlc);
+    packet.extend_from_slice(&data_bytes);
+    packet.push(0x00); // Reserved byte
+    let checksum: u8 = packet[2..].iter().map(|&b| b as u32).sum::<u32>() as u8 & 0xFF;
+    packet.push(checksum);
+
+    info!(
+        "FD send packet: {:02X?} (length: {} bytes, dlc={})",
+        packet,
+        packet.len(),
+        dlc
+    );
+    Ok(packet)
+}
+
 /// 创建 CAN 发送数据包（可变长度协议）
 ///
 /// 协议格式：
@@ -256,124 +432,269 @@ pub fn create_can_send_packet_variable(id: &str, data: &str, frame_type: &str) -
         }
     }
 
-    // Build packet
-    let mut packet = vec![0xAA]; // Start flag
+    // Build packet via the typed CanFrame/CanFrameBuilder (see can_frame.rs) instead of
+    // twiddling the bytes by hand here; the string parsing/validation above stays since
+    // that's specific to this "loose string in, Vec<u8> out" command-layer API.
+    let frame = crate::can_frame::CanFrameBuilder::new(can_id)
+        .extended(is_extended)
+        .data(data_bytes)
+        .build()
+        .map_err(|e| anyhow!("Failed to build CAN frame: {}", e))?;
+    let packet = frame
+        .to_variable_packet()
+        .map_err(|e| anyhow!("Failed to encode variable packet: {}", e))?;
 
-    // Control byte: bit7-6=11, bit5=frame_type, bit4=0(data frame), bit3-0=data_length
-    // Standard frame: 0xC0 | data_len (11000000 | data_len)
-    // Extended frame: 0xE0 | data_len (11100000 | data_len)
-    let control_byte = if is_extended {
-        0xE0 | (data_len as u8) // bit5=1 for extended frame
-    } else {
-        0xC0 | (data_len as u8) // bit5=0 for standard frame
+    info!("Send packet (variable): {:02X?} (length: {} bytes)", packet, packet.len());
+    Ok(packet)
+}
+
+/// 按 J1939 字段（优先级 + PGN + 源地址）构造一个可变长度协议的发送包
+pub fn create_can_send_packet_variable_j1939(
+    priority: u8,
+    pgn: u32,
+    source_address: u8,
+    data: &str,
+) -> Result<Vec<u8>> {
+    let j1939 = J1939Id {
+        priority,
+        pgn,
+        source_address,
+        destination_address: None,
     };
+    let id = format!("0x{:08X}", j1939.to_raw());
+    create_can_send_packet_variable(&id, data, "extended")
+}
+
+/// 创建 CAN FD 发送数据包（可变长度协议），支持 12/16/20/24/32/48/64 字节负载
+///
+/// 控制字节的 bit3-0 不再是原始字节数，而是 DLC（见 [`len_to_dlc`]），紧跟在控制字节
+/// 后面插入一个单独的 FD 标志字节（bit0=FDF 固定 1，bit1=BRS，bit2=ESI）；由于目前
+/// 变长协议只用来发送、没有接收端解析器，这个新加的标志字节不影响任何既有解码逻辑。
+pub fn create_can_send_packet_variable_fd(
+    id: &str,
+    data: &str,
+    frame_type: &str,
+    brs: bool,
+    esi: bool,
+) -> Result<Vec<u8>> {
+    let data_bytes = parse_hex_data_bytes(data)?;
+    let dlc = len_to_dlc(data_bytes.len()).ok_or_else(|| {
+        anyhow!(
+            "CAN FD payload length {} is not a valid size (0-8, 12, 16, 20, 24, 32, 48, 64)",
+            data_bytes.len()
+        )
+    })?;
+
+    let is_extended = frame_type == "extended";
+    let can_id = parse_and_validate_can_id(id, is_extended)?;
+
+    let mut packet = vec![0xAA]; // Start flag
+    let control_byte = if is_extended { 0xE0 | dlc } else { 0xC0 | dlc };
     packet.push(control_byte);
-    info!("Control byte: 0x{:02X} (extended={}, data_len={})", control_byte, is_extended, data_len);
 
-    // CAN ID (little-endian)
+    let mut fd_flags = 0x01; // bit0=FDF
+    if brs {
+        fd_flags |= 0x02;
+    }
+    if esi {
+        fd_flags |= 0x04;
+    }
+    packet.push(fd_flags);
+
     if is_extended {
-        // Extended frame: 4 bytes
-        let id_bytes = can_id.to_le_bytes();
-        packet.extend_from_slice(&id_bytes);
-        info!("Extended CAN ID bytes (4 bytes, little-endian): {:02X?}", id_bytes);
+        packet.extend_from_slice(&can_id.to_le_bytes());
     } else {
-        // Standard frame: 2 bytes (only lower 16 bits)
-        let id_u16 = (can_id & 0xFFFF) as u16;
-        let id_bytes = id_u16.to_le_bytes();
-        packet.extend_from_slice(&id_bytes);
-        info!("Standard CAN ID bytes (2 bytes, little-endian): {:02X?}", id_bytes);
+        packet.extend_from_slice(&((can_id & 0xFFFF) as u16).to_le_bytes());
     }
 
-    // Data content
     packet.extend_from_slice(&data_bytes);
-    // info!("Added data bytes: {:02X?}", data_bytes);
+    packet.push(0x55); // End flag
+
+    info!(
+        "FD send packet (variable): {:02X?} (length: {} bytes, dlc={})",
+        packet,
+        packet.len(),
+        dlc
+    );
+    Ok(packet)
+}
 
-    // End flag
-    packet.push(0x55);
+/// [`parse_received_can_message_checked`]/[`parse_received_can_message_unchecked`] 能
+/// 失败的具体原因；字段名故意和调用方最可能拿来做日志/上报的说法对齐
+/// （`got`/`expected`、收到的报头两个字节、非法的 DLC 值本身）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanParseError {
+    TooShort { got: usize, expected: usize },
+    BadHeader { byte0: u8, byte1: u8 },
+    InvalidDataLength(u8),
+    ChecksumMismatch { expected: u8, computed: u8 },
+}
 
-    info!("Send packet (variable): {:02X?} (length: {} bytes)", packet, packet.len());
-    Ok(packet)
+impl std::fmt::Display for CanParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanParseError::TooShort { got, expected } => {
+                write!(f, "CAN frame too short: got {} bytes, expected at least {}", got, expected)
+            }
+            CanParseError::BadHeader { byte0, byte1 } => write!(
+                f,
+                "invalid CAN frame header: expected 0xAA 0x55, got 0x{:02X} 0x{:02X}",
+                byte0, byte1
+            ),
+            CanParseError::InvalidDataLength(dlc) => write!(f, "invalid DLC byte: {}", dlc),
+            CanParseError::ChecksumMismatch { expected, computed } => write!(
+                f,
+                "CAN frame checksum mismatch: expected 0x{:02X}, computed 0x{:02X}",
+                expected, computed
+            ),
+        }
+    }
 }
 
-/// 解析接收到的 CAN 消息（固定20字节协议）
-/// 
-/// 协议格式（20字节）:
+impl std::error::Error for CanParseError {}
+
+/// 解析成功后的结构化接收消息；字段含义和原来 `parse_received_can_message` 返回的
+/// 三元组一致（CAN ID/数据 的十六进制字符串形式 + 帧类型），只是换成具名字段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanRxMessage {
+    pub can_id: String,
+    pub can_data: String,
+    pub frame_type: String,
+    /// 发送方挂在这一帧上的可选元数据（见 [`FrameHeader`]），没有时为 `None`
+    pub header: Option<FrameHeader>,
+}
+
+fn can_frame_to_rx_message(frame: &CanFrame) -> CanRxMessage {
+    let can_id = format!("0x{:08X}", frame.id);
+    let can_data = frame
+        .data
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let base_type = if frame.extended { "extended" } else { "standard" };
+    let frame_type = if frame.fd {
+        format!("{}_fd", base_type)
+    } else {
+        base_type.to_string()
+    };
+
+    CanRxMessage { can_id, can_data, frame_type, header: frame.header }
+}
+
+/// 解析接收到的 CAN 消息（固定协议，经典帧20字节，CAN FD 帧按 DLC 变长）为强类型的
+/// [`CanFrame`]，失败时返回具体原因而不是笼统的 `None`——帧头、长度、DLC、校验和
+/// 各自有单独的 [`CanParseError`] 变体，UI/日志层可以按需分别提示
+///
+/// 协议格式:
 /// - 字节0: 数据包报头 (0xAA)
 /// - 字节1: 数据包报头 (0x55)
 /// - 字节2: 类型 (0x01)
-/// - 字节3: 框架类型 (0x01)
-/// - 字节4: 框架模式 (0x01)
+/// - 字节3: 框架类型 (0x01=标准, 0x02=扩展)
+/// - 字节4: 框架模式 (bit0=1 固定标记位, bit1=FDF, bit2=BRS, bit3=ESI)
 /// - 字节5-8: CAN ID (4字节, 小端序)
-/// - 字节9: 数据长度 (0x08)
-/// - 字节10-17: CAN数据 (8字节)
-/// - 字节18: 保留 (0x00)
-/// - 字节19: 检查代码 (校验和)
-pub fn parse_received_can_message(data: &[u8]) -> Option<(String, String, String)> {
-    if data.len() < 20 {
-        println!("❌ [Parse] Data too short: {} bytes (need 20)", data.len());
-        return None;
+/// - 字节9: DLC（经典帧里这个值就等于数据字节数，和 CAN FD 共用同一套解码，
+///   见 [`dlc_to_len`]）
+/// - 字节10..10+N: CAN数据 (N = dlc_to_len(DLC))
+/// - 字节10+N: 保留 (0x00)
+/// - 字节11+N: 检查代码 (校验和)
+///
+/// 字段布局的校验交给 `can_frame.rs` 的 [`Packet::new_checked`]，这里只是把它的
+/// [`can_frame::Error`](crate::can_frame::Error) 翻译成这个模块对外的 [`CanParseError`]；
+/// 也是 [`CanFrame::from_rx_message`](crate::can_frame::CanFrame::from_rx_message) 复用的
+/// 同一份校验逻辑，两者失败时报的原因完全一致
+pub fn parse_can_frame_checked(data: &[u8]) -> std::result::Result<CanFrame, CanParseError> {
+    // 帧头不对时单独处理，这样才能在错误里带上实际收到的两个字节
+    // （`can_frame::Error::InvalidHeader` 本身不携带这两个字节）
+    if data.len() < 2 {
+        return Err(CanParseError::TooShort { got: data.len(), expected: 2 });
     }
-
     if data[0] != 0xAA || data[1] != 0x55 {
-        println!("❌ [Parse] Invalid frame header: {:02X} {:02X}", data[0], data[1]);
-        return None;
+        return Err(CanParseError::BadHeader { byte0: data[0], byte1: data[1] });
     }
 
-    println!("🔍 [Parse] Fixed 20-byte protocol");
-    println!("🔍 [Parse] Type: 0x{:02X}, Frame Type: 0x{:02X}, Frame Mode: 0x{:02X}",
-             data[2], data[3], data[4]);
-
-    // Parse frame type (byte 3)
-    // 0x01 = Standard frame, 0x02 = Extended frame
-    let frame_type_byte = data[3];
-    let frame_type = match frame_type_byte {
-        0x01 => "standard",
-        0x02 => "extended",
-        _ => "unknown",
-    };
-    println!("🔍 [Parse] Frame Type: {} (0x{:02X})", frame_type, frame_type_byte);
-
-    // Parse CAN ID (bytes 5-8, little-endian)
-    let can_id = (data[5] as u32) |
-                 ((data[6] as u32) << 8) |
-                 ((data[7] as u32) << 16) |
-                 ((data[8] as u32) << 24);
+    let packet = Packet::new_checked(data).map_err(|e| match e {
+        crate::can_frame::Error::TooShort { expected, got } => {
+            CanParseError::TooShort { got, expected }
+        }
+        crate::can_frame::Error::InvalidHeader => {
+            CanParseError::BadHeader { byte0: data[0], byte1: data[1] }
+        }
+        crate::can_frame::Error::InvalidDlc(dlc) => CanParseError::InvalidDataLength(dlc),
+        crate::can_frame::Error::ChecksumMismatch { expected, got } => {
+            CanParseError::ChecksumMismatch { expected, computed: got }
+        }
+        crate::can_frame::Error::IdOutOfRange { .. } | crate::can_frame::Error::HeaderNotSupported => {
+            unreachable!("Packet::new_checked never reports {:?}", e)
+        }
+    })?;
 
-    println!("🔍 [Parse] CAN ID bytes: {:02X} {:02X} {:02X} {:02X} -> 0x{:08X}",
-             data[5], data[6], data[7], data[8], can_id);
+    Ok(CanFrame::parse(&packet))
+}
 
-    // Data length (byte 9)
-    let data_len = data[9] as usize;
-    println!("🔍 [Parse] Data length: {}", data_len);
+/// [`parse_can_frame_checked`] 的字符串化版本，返回和历史接口一致的三个具名字符串字段
+pub fn parse_received_can_message_checked(
+    data: &[u8],
+) -> std::result::Result<CanRxMessage, CanParseError> {
+    Ok(can_frame_to_rx_message(&parse_can_frame_checked(data)?))
+}
 
-    if data_len > 8 {
-        println!("❌ [Parse] Invalid data length: {} (max 8)", data_len);
-        return None;
+/// 跳过帧头/校验和校验的快速解析，供已经确认来源可信（比如同一条链路上刚发过去
+/// 又自己收回来做自检）的场景使用；DLC 越界仍然会报错，因为这是安全取数据切片
+/// 必须有的边界检查，不是"协议层面"的校验
+pub fn parse_received_can_message_unchecked(
+    data: &[u8],
+) -> std::result::Result<CanRxMessage, CanParseError> {
+    if data.len() < 10 {
+        return Err(CanParseError::TooShort { got: data.len(), expected: 10 });
     }
 
-    // Extract CAN data (bytes 10-17)
-    let can_data = data[10..10 + data_len]
-        .iter()
-        .map(|b| format!("{:02X}", b))
-        .collect::<Vec<_>>()
-        .join(" ");
-
-    println!("🔍 [Parse] CAN Data: {}", can_data);
+    let packet = Packet::new_unchecked(data);
+    let dlc = packet.dlc();
+    let data_len = dlc_to_len(dlc).ok_or(CanParseError::InvalidDataLength(dlc))?;
 
-    // Verify checksum (byte 19)
-    let checksum_received = data[19];
-    let checksum_calculated: u8 = data[2..19].iter().map(|&b| b as u32).sum::<u32>() as u8;
+    let checksum_index = 10 + data_len + 1;
+    if data.len() < checksum_index + 1 {
+        return Err(CanParseError::TooShort {
+            got: data.len(),
+            expected: checksum_index + 1,
+        });
+    }
 
-    println!("🔍 [Parse] Checksum - Received: 0x{:02X}, Calculated: 0x{:02X}",
-             checksum_received, checksum_calculated);
+    Ok(can_frame_to_rx_message(&CanFrame::parse(&packet)))
+}
 
-    if checksum_received != checksum_calculated {
-        println!("⚠️  [Parse] Checksum mismatch!");
+/// 解析接收到的 CAN 消息，返回和历史版本一致的 `(can_id, can_data, frame_type)` 三元组；
+/// 现在是 [`parse_received_can_message_checked`] 的一个瘦包装，失败原因只打印一条日志、
+/// 不返回给调用方，需要具体原因的新调用方应该直接用 checked 版本
+pub fn parse_received_can_message(data: &[u8]) -> Option<(String, String, String)> {
+    match parse_received_can_message_checked(data) {
+        Ok(msg) => {
+            println!(
+                "✅ [Parse] Successfully parsed - ID: {}, Data: {}, Frame Type: {}",
+                msg.can_id, msg.can_data, msg.frame_type
+            );
+            Some((msg.can_id, msg.can_data, msg.frame_type))
+        }
+        Err(e) => {
+            println!("❌ [Parse] {}", e);
+            None
+        }
     }
+}
 
-    let can_id_str = format!("0x{:08X}", can_id);
-    println!("✅ [Parse] Successfully parsed - ID: {}, Data: {}, Frame Type: {}", can_id_str, can_data, frame_type);
-    Some((can_id_str, can_data, frame_type.to_string()))
+/// 如果 `can_id`（"0xNNNNNNNN" 形式）来自扩展帧，拆出其 J1939 字段；标准帧没有
+/// 29 位可拆，直接返回 `None`
+///
+/// 供 `parse_received_can_message` 的调用方按需调用，不强制塞进每条消息里
+/// （参见 `io_thread.rs` 里 `decode_signals` 的用法，两者都是"可选附加解码"）
+pub fn decode_j1939(can_id: &str, frame_type: &str) -> Option<J1939Id> {
+    if !frame_type.starts_with("extended") {
+        return None;
+    }
+    let id_hex = can_id.strip_prefix("0x").or_else(|| can_id.strip_prefix("0X"))?;
+    let id = u32::from_str_radix(id_hex, 16).ok()?;
+    Some(J1939Id::from_raw(id))
 }
 
 /// 从 CAN 数据中解析距离值（取最后两个字节）
@@ -449,6 +770,12 @@ mod tests {
             frame_type: "standard".to_string(),
             can_mode: "normal".to_string(),
             protocol_length: "fixed".to_string(),
+            framing: "sum8header".to_string(),
+            reconnect: false,
+            max_backoff_ms: 10_000,
+            tx_queue_size: 256,
+            can_fd_enabled: false,
+            data_baud_rate: 2_000_000,
         };
 
         let packet = create_can_config_packet(&config);
@@ -471,6 +798,12 @@ mod tests {
             frame_type: "extended".to_string(),
             can_mode: "loopback".to_string(),
             protocol_length: "variable".to_string(),
+            framing: "sum8header".to_string(),
+            reconnect: false,
+            max_backoff_ms: 10_000,
+            tx_queue_size: 256,
+            can_fd_enabled: false,
+            data_baud_rate: 2_000_000,
         };
 
         let packet = create_can_config_packet(&config);
@@ -491,6 +824,12 @@ mod tests {
             frame_type: "standard".to_string(),
             can_mode: "normal".to_string(),
             protocol_length: "fixed".to_string(),
+            framing: "sum8header".to_string(),
+            reconnect: false,
+            max_backoff_ms: 10_000,
+            tx_queue_size: 256,
+            can_fd_enabled: false,
+            data_baud_rate: 2_000_000,
         };
 
         let packet = create_can_config_packet(&config);
@@ -801,6 +1140,95 @@ mod tests {
         assert_eq!(frame_type, "extended", "Frame type should be extended");
     }
 
+    // ==================== parse_received_can_message_checked / _unchecked 测试 ====================
+
+    #[test]
+    fn test_parse_received_can_message_checked_reports_bad_header() {
+        let data = vec![
+            0xBB, 0x55, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x08, 0x01, 0x83, 0x02, 0x02,
+            0xF2, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let err = parse_received_can_message_checked(&data).unwrap_err();
+        assert_eq!(err, CanParseError::BadHeader { byte0: 0xBB, byte1: 0x55 });
+    }
+
+    #[test]
+    fn test_parse_received_can_message_checked_reports_invalid_dlc() {
+        let mut data = vec![
+            0xAA, 0x55, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x10, 0x00, 0x00,
+        ];
+        let checksum: u8 = data[2..data.len() - 1].iter().map(|&b| b as u32).sum::<u32>() as u8;
+        data.push(checksum);
+
+        let err = parse_received_can_message_checked(&data).unwrap_err();
+        assert_eq!(err, CanParseError::InvalidDataLength(0x10));
+    }
+
+    #[test]
+    fn test_parse_received_can_message_checked_reports_checksum_mismatch() {
+        let mut data = vec![
+            0xAA, 0x55, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x08, 0x01, 0x83, 0x02, 0x02,
+            0xF2, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let checksum: u8 = data[2..19].iter().map(|&b| b as u32).sum::<u32>() as u8;
+        data.push(checksum ^ 0xFF);
+
+        let err = parse_received_can_message_checked(&data).unwrap_err();
+        assert!(matches!(err, CanParseError::ChecksumMismatch { .. }));
+    }
+
+    #[test]
+    fn test_parse_received_can_message_checked_matches_legacy_option_api() {
+        let mut data = vec![
+            0xAA, 0x55, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x08, 0x01, 0x83, 0x02, 0x02,
+            0xF2, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let checksum: u8 = data[2..19].iter().map(|&b| b as u32).sum::<u32>() as u8;
+        data.push(checksum);
+
+        let msg = parse_received_can_message_checked(&data).unwrap();
+        let (can_id, can_data, frame_type) = parse_received_can_message(&data).unwrap();
+        assert_eq!(msg.can_id, can_id);
+        assert_eq!(msg.can_data, can_data);
+        assert_eq!(msg.frame_type, frame_type);
+    }
+
+    #[test]
+    fn test_parse_received_can_message_unchecked_ignores_bad_checksum() {
+        let mut data = vec![
+            0xAA, 0x55, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x08, 0x01, 0x83, 0x02, 0x02,
+            0xF2, 0x00, 0x00, 0x00, 0x00,
+        ];
+        data.push(0x00); // deliberately wrong checksum
+
+        let msg = parse_received_can_message_unchecked(&data).unwrap();
+        assert_eq!(msg.can_id, "0x18C4D2D0");
+        assert_eq!(msg.can_data, "01 83 02 02 F2 00 00 00");
+    }
+
+    #[test]
+    fn test_parse_received_can_message_unchecked_ignores_bad_header() {
+        let mut data = vec![
+            0x00, 0x00, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x08, 0x01, 0x83, 0x02, 0x02,
+            0xF2, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let checksum: u8 = data[2..19].iter().map(|&b| b as u32).sum::<u32>() as u8;
+        data.push(checksum);
+
+        assert!(parse_received_can_message_unchecked(&data).is_ok());
+    }
+
+    #[test]
+    fn test_parse_received_can_message_unchecked_still_rejects_invalid_dlc() {
+        let data = vec![
+            0xAA, 0x55, 0x01, 0x01, 0x01, 0xD0, 0xD2, 0xC4, 0x18, 0x10, 0x00, 0x00,
+        ];
+        assert_eq!(
+            parse_received_can_message_unchecked(&data).unwrap_err(),
+            CanParseError::InvalidDataLength(0x10)
+        );
+    }
+
     // ==================== parse_distance_from_data 测试 ====================
 
     #[test]
@@ -988,4 +1416,129 @@ mod tests {
         assert_eq!(data.len(), 20, "Message should be 20 bytes");
         assert_eq!(checksum, 0x00, "Checksum of all zeros should be 0x00");
     }
+
+    // ==================== CAN FD DLC 映射测试 ====================
+
+    #[test]
+    fn test_dlc_to_len_classic_range() {
+        for n in 0..=8u8 {
+            assert_eq!(dlc_to_len(n), Some(n as usize));
+        }
+    }
+
+    #[test]
+    fn test_dlc_to_len_fd_range() {
+        assert_eq!(dlc_to_len(9), Some(12));
+        assert_eq!(dlc_to_len(10), Some(16));
+        assert_eq!(dlc_to_len(11), Some(20));
+        assert_eq!(dlc_to_len(12), Some(24));
+        assert_eq!(dlc_to_len(13), Some(32));
+        assert_eq!(dlc_to_len(14), Some(48));
+        assert_eq!(dlc_to_len(15), Some(64));
+    }
+
+    #[test]
+    fn test_dlc_to_len_out_of_range() {
+        assert_eq!(dlc_to_len(16), None);
+    }
+
+    #[test]
+    fn test_len_to_dlc_round_trips_valid_lengths() {
+        for len in [0usize, 1, 8, 12, 16, 20, 24, 32, 48, 64] {
+            let dlc = len_to_dlc(len).unwrap_or_else(|| panic!("length {} should map to a DLC", len));
+            assert_eq!(dlc_to_len(dlc), Some(len));
+        }
+    }
+
+    #[test]
+    fn test_len_to_dlc_rejects_invalid_length() {
+        assert_eq!(len_to_dlc(9), None, "9 bytes is not a valid CAN FD payload size");
+        assert_eq!(len_to_dlc(100), None);
+    }
+
+    // ==================== create_can_send_packet_fixed_fd 测试 ====================
+
+    #[test]
+    fn test_create_can_send_packet_fixed_fd_16_bytes() {
+        let data = "11 22 33 44 55 66 77 88 99 AA BB CC DD EE FF 00";
+        let result = create_can_send_packet_fixed_fd("0x123", data, "standard", false, false);
+        assert!(result.is_ok(), "Should accept a 16-byte FD payload");
+
+        let packet = result.unwrap();
+        assert_eq!(packet[9], 10, "DLC for 16 bytes should be 10");
+        assert_eq!(packet.len(), 12 + 16, "Packet should be header+id+dlc+16 data+reserved+checksum");
+        assert_eq!(packet[4] & 0x02, 0x02, "Frame mode byte should have the FDF bit set");
+    }
+
+    #[test]
+    fn test_create_can_send_packet_fixed_fd_brs_esi_flags() {
+        let result = create_can_send_packet_fixed_fd("0x123", "11 22 33 44 55 66 77 88 99 AA BB CC", "standard", true, true);
+        let packet = result.unwrap();
+        assert_eq!(packet[4] & 0x0F, 0x0F, "FDF+BRS+ESI+const bits should all be set");
+    }
+
+    #[test]
+    fn test_create_can_send_packet_fixed_fd_rejects_invalid_length() {
+        let result = create_can_send_packet_fixed_fd("0x123", "11 22 33 44 55 66 77 88 99", "standard", false, false);
+        assert!(result.is_err(), "9-byte payload is not a valid CAN FD size");
+    }
+
+    // ==================== create_can_send_packet_variable_fd 测试 ====================
+
+    #[test]
+    fn test_create_can_send_packet_variable_fd_32_bytes() {
+        let data: String = (0..32).map(|_| "11").collect::<Vec<_>>().join(" ");
+        let result = create_can_send_packet_variable_fd("0x1234567", &data, "extended", false, false);
+        assert!(result.is_ok(), "Should accept a 32-byte FD payload on an extended frame");
+
+        let packet = result.unwrap();
+        assert_eq!(packet[1] & 0x0F, 13, "Control byte DLC nibble for 32 bytes should be 13");
+        assert_eq!(packet[2] & 0x01, 0x01, "FD flags byte should have the FDF bit set");
+    }
+
+    #[test]
+    fn test_create_can_send_packet_variable_fd_rejects_invalid_length() {
+        let result = create_can_send_packet_variable_fd("0x123", "11 22 33", "standard", false, false);
+        assert!(result.is_err(), "3-byte payload is not a valid CAN FD size");
+    }
+
+    // ==================== parse_received_can_message（CAN FD）测试 ====================
+
+    #[test]
+    fn test_parse_received_can_message_fd_round_trip() {
+        let data_hex = "11 22 33 44 55 66 77 88 99 AA BB CC"; // 12 bytes -> DLC 9
+        let packet = create_can_send_packet_fixed_fd("0x123", data_hex, "standard", true, false).unwrap();
+
+        let result = parse_received_can_message(&packet);
+        assert!(result.is_some(), "Should parse a CAN FD frame");
+
+        let (can_id, can_data, frame_type) = result.unwrap();
+        assert_eq!(can_id, "0x00000123");
+        assert_eq!(can_data, "11 22 33 44 55 66 77 88 99 AA BB CC");
+        assert_eq!(frame_type, "standard_fd", "Frame type should be tagged as FD");
+    }
+
+    #[test]
+    fn test_parse_received_can_message_classic_frame_still_not_fd() {
+        let packet = create_can_send_packet_fixed("0x123", "11 22 33 44", "standard").unwrap();
+        let result = parse_received_can_message(&packet);
+        let (_, _, frame_type) = result.unwrap();
+        assert_eq!(frame_type, "standard", "Classic frames should not get the _fd suffix");
+    }
+
+    #[test]
+    fn test_parse_received_can_message_rejects_invalid_dlc() {
+        let mut data = vec![
+            0xAA, 0x55, 0x01, 0x01, 0x01,
+            0xD0, 0xD2, 0xC4, 0x18,
+            0x10, // Invalid DLC (only 0-15 are defined, 16 is out of range)
+            0x00,
+            0x00,
+        ];
+        let checksum: u8 = data[2..data.len() - 1].iter().map(|&b| b as u32).sum::<u32>() as u8;
+        data.push(checksum);
+
+        let result = parse_received_can_message(&data);
+        assert!(result.is_none(), "DLC 16 is out of the defined CAN FD range");
+    }
 }
@@ -1,5 +1,5 @@
 use std::sync::atomic::Ordering;
-use std::sync::{mpsc, Arc};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
@@ -11,9 +11,12 @@ use tauri::{Manager, State};
 use crate::can_protocol::{
     create_can_config_packet, create_can_send_packet_fixed, create_can_send_packet_variable,
 };
-use crate::infinite_loop::run_infinite_drive;
+use crate::infinite_loop::{Trajectory, run_infinite_drive};
 use crate::io_thread::start_io_thread;
+use crate::isotp;
+use crate::scheduler::CyclicJobInfo;
 use crate::system_monitor_thread::start_system_monitor_thread;
+use crate::tx_queue::{TxQueue, TxQueueStats};
 use crate::{AppState, SendMessage, SerialConfig};
 
 /// Get available serial ports
@@ -37,10 +40,10 @@ async fn send_can_config(state: &State<'_, AppState>, config: &SerialConfig) ->
     let packet = create_can_config_packet(config);
 
     let tx_send = state.tx_send.lock().unwrap();
-    if let Some(ref sender) = *tx_send {
-        sender.send(SendMessage { packet }).map_err(|e| {
-            error!("Failed to send config packet through channel: {}", e);
-            anyhow!("Failed to send config packet")
+    if let Some(ref queue) = *tx_send {
+        queue.try_enqueue(SendMessage { packet }).map_err(|_| {
+            error!("TX queue full, dropped config packet");
+            anyhow!("TX queue full, failed to send config packet")
         })?;
         info!("Config packet sent through channel");
     } else {
@@ -87,14 +90,15 @@ pub async fn connect_serial(
         }
     };
 
-    // Create send channel
-    println!("📡 [Connect] Creating send channel");
-    let (tx_send, rx_send) = mpsc::channel();
+    // Create bounded send queue - a fast producer (CSV loop, MQTT bridge) can't pile up
+    // unbounded memory anymore, it has to deal with backpressure once this fills up
+    println!("📡 [Connect] Creating bounded send queue (capacity {})", config.tx_queue_size);
+    let (tx_queue, rx_send, tx_depth) = TxQueue::bounded(config.tx_queue_size);
 
     // Save sender to state
     {
         let mut tx_send_guard = state.tx_send.lock().unwrap();
-        *tx_send_guard = Some(tx_send);
+        *tx_send_guard = Some(tx_queue.clone());
     }
 
     // Set connection state
@@ -103,6 +107,16 @@ pub async fn connect_serial(
         *is_connected = true;
     }
 
+    {
+        let mut active_port = state.active_port.lock().unwrap();
+        *active_port = Some(config.port.clone());
+    }
+
+    // 热插拔监控是常驻的，断开连接时会停掉它，这里保证重新连接后它又跟着活过来
+    if !state.port_monitor_running.load(Ordering::SeqCst) {
+        crate::port_monitor::start_port_monitor(app_handle.clone(), state.inner().clone());
+    }
+
     // Send CAN config through channel
     println!("⚙️  [Connect] Sending CAN configuration");
     if let Err(e) = send_can_config(&state, &config).await {
@@ -110,10 +124,32 @@ pub async fn connect_serial(
         println!("⚠️  [Connect] Failed to send CAN configuration: {}", e);
     }
 
-    // Start I/O thread
-    println!("🧵 [Connect] Starting I/O thread");
+    // Register the port with the shared reactor
+    println!("🧵 [Connect] Registering CAN port with reactor");
+    let framing = crate::framing::from_name(&config.framing);
     let state_clone = state.inner().clone();
-    start_io_thread(port, state_clone, rx_send, app_handle);
+    *state.last_activity.lock().unwrap() = std::time::Instant::now();
+    let port_id = start_io_thread(
+        port,
+        state_clone,
+        rx_send,
+        app_handle.clone(),
+        framing,
+        config.clone(),
+        tx_depth,
+    );
+    {
+        let mut io_port_id = state.io_port_id.lock().unwrap();
+        *io_port_id = Some(port_id);
+    }
+
+    // 队列是否持续满载也是背压信号之一，交给独立线程盯着，跟写线程的生命周期绑定
+    tx_queue.start_saturation_watcher(app_handle.clone(), state.write_thread_running.clone());
+
+    // 链路心跳只在前端开启了自动重连时才需要跑
+    if config.reconnect {
+        crate::reconnect::start_heartbeat(app_handle, state.inner().clone(), config);
+    }
 
     println!("✅ [Connect] Serial port connected successfully - Ready to receive messages!");
     info!("Serial port connected successfully");
@@ -133,11 +169,27 @@ pub async fn disconnect_serial(state: State<'_, AppState>) -> Result<String, Str
         *tx_send = None;
     }
 
+    {
+        let mut io_port_id = state.io_port_id.lock().unwrap();
+        if let Some(port_id) = io_port_id.take() {
+            crate::reactor::Reactor::global().remove_port(port_id);
+        }
+    }
+
     {
         let mut is_connected = state.is_connected.lock().unwrap();
         *is_connected = false;
     }
 
+    {
+        let mut active_port = state.active_port.lock().unwrap();
+        *active_port = None;
+    }
+
+    state.port_monitor_running.store(false, Ordering::SeqCst);
+    // 主动断开不是故障，取消掉可能正在跑的重连退避循环（心跳线程会在下一轮自行退出）
+    state.reconnect_running.store(false, Ordering::SeqCst);
+
     thread::sleep(Duration::from_millis(100));
 
     info!("Serial port disconnected");
@@ -200,15 +252,15 @@ pub async fn send_can_message(
     // info!("Packet content: {:02X?}", packet);
 
     let tx_send = state.tx_send.lock().unwrap();
-    if let Some(ref sender) = *tx_send {
-        match sender.send(SendMessage { packet }) {
-            Ok(_) => {
+    if let Some(ref queue) = *tx_send {
+        match queue.try_enqueue(SendMessage { packet }) {
+            Ok(()) => {
                 // info!("CAN message sent to write thread successfully!");
                 Ok("Message sent successfully".to_string())
             }
-            Err(e) => {
-                error!("Failed to send message through channel: {}", e);
-                Err(format!("Failed to send message: {}", e))
+            Err(_) => {
+                warn!("TX queue full, message dropped");
+                Err("TX queue is full, message dropped".to_string())
             }
         }
     } else {
@@ -217,6 +269,60 @@ pub async fn send_can_message(
     }
 }
 
+/// 查询当前有界发送队列的深度/容量/累计入队/丢弃数，供前端展示链路是否饱和
+#[tauri::command]
+pub async fn get_tx_queue_stats(state: State<'_, AppState>) -> Result<TxQueueStats, String> {
+    let tx_send = state.tx_send.lock().unwrap();
+    match *tx_send {
+        Some(ref queue) => Ok(queue.stats()),
+        None => Err("Not connected".to_string()),
+    }
+}
+
+/// 注册一个周期发送任务（心跳、固定频率控制指令等），由 I/O 线程的定时轮驱动发送
+#[tauri::command]
+pub async fn schedule_cyclic_message(
+    id: String,
+    data: String,
+    frame_type: String,
+    protocol_length: String,
+    period_ms: u64,
+    repeat: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    info!(
+        "Scheduling cyclic message - ID: {}, period: {}ms, repeat: {:?}",
+        id, period_ms, repeat
+    );
+
+    let packet = if protocol_length == "variable" {
+        create_can_send_packet_variable(&id, &data, &frame_type)
+    } else {
+        create_can_send_packet_fixed(&id, &data, &frame_type)
+    }
+    .map_err(|e| format!("Failed to create packet: {}", e))?;
+
+    let job_id = state
+        .cyclic_scheduler
+        .lock()
+        .unwrap()
+        .schedule(packet, period_ms, repeat);
+
+    Ok(job_id)
+}
+
+/// 取消一个周期发送任务
+#[tauri::command]
+pub async fn cancel_cyclic_message(job_id: u64, state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.cyclic_scheduler.lock().unwrap().cancel(job_id))
+}
+
+/// 列出当前仍在运行的周期发送任务
+#[tauri::command]
+pub async fn list_cyclic_messages(state: State<'_, AppState>) -> Result<Vec<CyclicJobInfo>, String> {
+    Ok(state.cyclic_scheduler.lock().unwrap().list())
+}
+
 /// 打开系统监控窗口
 #[tauri::command]
 pub async fn open_system_monitor_window(app_handle: tauri::AppHandle) -> Result<(), String> {
@@ -288,7 +394,11 @@ pub async fn connect_system_monitor(
     }
 
     let state_clone = state.inner().clone();
-    start_system_monitor_thread(port, state_clone, app_handle);
+    let port_id = start_system_monitor_thread(port, state_clone, app_handle);
+    {
+        let mut system_monitor_port_id = state.system_monitor_port_id.lock().unwrap();
+        *system_monitor_port_id = Some(port_id);
+    }
 
     Ok("Connected to System Monitor".to_string())
 }
@@ -300,6 +410,13 @@ pub async fn disconnect_system_monitor(state: State<'_, AppState>) -> Result<Str
         .system_monitor_thread_running
         .store(false, Ordering::SeqCst);
 
+    {
+        let mut system_monitor_port_id = state.system_monitor_port_id.lock().unwrap();
+        if let Some(port_id) = system_monitor_port_id.take() {
+            crate::reactor::Reactor::global().remove_port(port_id);
+        }
+    }
+
     {
         let mut is_connected = state.system_monitor_connected.lock().unwrap();
         *is_connected = false;
@@ -309,8 +426,17 @@ pub async fn disconnect_system_monitor(state: State<'_, AppState>) -> Result<Str
 }
 
 /// Start Infinite Algorithmic Drive
+///
+/// `config` carries the closed-loop feedback controller's gains and feedback CAN ID (see
+/// `infinite_loop::FeedbackConfig`), plus the trajectory to drive: `trajectory`/`trajectory_csv`,
+/// `interpolation` and `cycle_duration_sec` (see `infinite_loop::Trajectory::prepare_from_config`).
+/// An empty `{}` falls back to all of their defaults, including the original built-in scenario.
+/// The trajectory is parsed once here, synchronously, so a malformed `config` is rejected before
+/// the drive loop ever starts; calling this again with a different `config` is how the frontend
+/// switches scenarios live.
 #[tauri::command]
 pub async fn start_infinite_drive(
+    config: serde_json::Value,
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
 ) -> Result<String, String> {
@@ -327,20 +453,13 @@ pub async fn start_infinite_drive(
         }
     }
 
-    state.auto_drive_running.store(true, Ordering::SeqCst);
+    let trajectory = Trajectory::prepare_from_config(&config).map_err(|e| e.to_string())?;
 
-    let state_clone = Arc::new(AppState {
-        tx_send: state.tx_send.clone(),
-        is_connected: state.is_connected.clone(),
-        auto_drive_running: state.auto_drive_running.clone(),
-        receive_thread_running: state.receive_thread_running.clone(),
-        write_thread_running: state.write_thread_running.clone(),
-        system_monitor_connected: state.system_monitor_connected.clone(),
-        system_monitor_thread_running: state.system_monitor_thread_running.clone(),
-    });
+    state.auto_drive_running.store(true, Ordering::SeqCst);
 
+    let state_clone = Arc::new(state.inner().clone());
     std::thread::spawn(move || {
-        if let Err(e) = run_infinite_drive(state_clone, app_handle) {
+        if let Err(e) = run_infinite_drive(state_clone, app_handle, config, trajectory) {
             error!("Infinite drive error: {}", e);
         }
     });
@@ -356,3 +475,110 @@ pub async fn stop_infinite_drive(state: State<'_, AppState>) -> Result<String, S
     thread::sleep(Duration::from_millis(100));
     Ok("Infinite drive stopped".to_string())
 }
+
+/// 启动 MQTT 桥接：把收到的 CAN 帧发布出去，并允许远程通过 MQTT 下发发送请求。
+/// 仅在编译时开启 `mqtt` cargo feature 才会真正连接 broker，详见 `mqtt_bridge.rs`。
+#[cfg(feature = "mqtt")]
+#[tauri::command]
+pub async fn connect_mqtt_bridge(
+    broker_url: String,
+    base_topic: String,
+    qos: u8,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    info!("Connecting MQTT bridge to {} (topic: {})", broker_url, base_topic);
+    crate::mqtt_bridge::start_mqtt_bridge(app_handle, state.inner().clone(), broker_url, base_topic, qos)?;
+    Ok("MQTT bridge connected".to_string())
+}
+
+#[cfg(not(feature = "mqtt"))]
+#[tauri::command]
+pub async fn connect_mqtt_bridge(
+    _broker_url: String,
+    _base_topic: String,
+    _qos: u8,
+    _state: State<'_, AppState>,
+    _app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    Err("MQTT bridge support not compiled in (missing 'mqtt' feature)".to_string())
+}
+
+/// 停止 MQTT 桥接
+#[cfg(feature = "mqtt")]
+#[tauri::command]
+pub async fn disconnect_mqtt_bridge(state: State<'_, AppState>) -> Result<String, String> {
+    info!("Disconnecting MQTT bridge");
+    crate::mqtt_bridge::stop_mqtt_bridge(&state);
+    Ok("MQTT bridge disconnected".to_string())
+}
+
+#[cfg(not(feature = "mqtt"))]
+#[tauri::command]
+pub async fn disconnect_mqtt_bridge(_state: State<'_, AppState>) -> Result<String, String> {
+    Err("MQTT bridge support not compiled in (missing 'mqtt' feature)".to_string())
+}
+
+/// 解析并执行一段 SCPI 风格的脚本文本（见 `script_console.rs`），在独立线程上跑完
+#[tauri::command]
+pub async fn execute_script(
+    text: String,
+    config: SerialConfig,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    {
+        let is_connected = state.is_connected.lock().unwrap();
+        if !*is_connected {
+            return Err("Not connected".to_string());
+        }
+    }
+
+    if state.script_running.load(Ordering::SeqCst) {
+        return Err("A script is already running".to_string());
+    }
+
+    let commands = crate::script_console::parse_script(&text).map_err(|e| e.to_string())?;
+
+    info!("Executing script with {} top-level command(s)", commands.len());
+    crate::script_console::run_script(commands, config, state.inner().clone(), app_handle);
+
+    Ok("Script started".to_string())
+}
+
+/// 请求中止正在运行的脚本；执行线程会在下一条指令前检查并自行退出
+#[tauri::command]
+pub async fn stop_script(state: State<'_, AppState>) -> Result<String, String> {
+    state.script_running.store(false, Ordering::SeqCst);
+    Ok("Script stop requested".to_string())
+}
+
+/// 按 ISO-TP（ISO 15765-2）把任意长度的负载分段发到 `can_id`；传输要等 ECU 回
+/// Flow Control、要按 STmin 睡等待，所以和 `execute_script` 一样把实际工作丢到
+/// 后台线程里跑，命令本身校验完参数就立刻返回
+#[tauri::command]
+pub async fn send_isotp(
+    can_id: String,
+    payload: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    {
+        let is_connected = state.is_connected.lock().unwrap();
+        if !*is_connected {
+            return Err("Not connected".to_string());
+        }
+    }
+
+    let (can_id, payload) = isotp::parse_send_isotp_args(&can_id, &payload).map_err(|e| e.to_string())?;
+
+    info!("Starting ISO-TP transfer to 0x{:08X} ({} byte payload)", can_id, payload.len());
+    let state = state.inner().clone();
+    thread::spawn(move || {
+        if let Err(e) = isotp::send_isotp(can_id, payload, state, app_handle) {
+            error!("ISO-TP transfer failed: {}", e);
+        }
+    });
+
+    Ok("ISO-TP transfer started".to_string())
+}
@@ -1,91 +1,75 @@
 //! CSV 循环相关的函数
 //! 包括：CSV 数据读取、循环处理、发送等功能
+//!
+//! 回放分成两段（参考 ARTIQ 的 DDMA：录好的序列"编译"一次，之后随便放多少遍都不用
+//! 重新编译）：
+//! - [`prepare_csv_sequence`]/[`prepare_preloaded_sequence`] 一次性把 CSV 解析、
+//!   `extract_vehicle_control`、`create_can_send_packet_*` 全部做完，产出一个不透明的
+//!   [`SequenceHandle`]；
+//! - [`replay`] 是时间敏感的热循环，只做 channel 发送和 `thread::sleep`，不再解析 CSV、
+//!   不再构造发送包；每条记录发送时仍会克隆一份预编译好的包字节交给发送队列，进度事件的
+//!   JSON 也是现场生成的。同一个 handle 可以配合 `repeat_count` 反复回放同一段轨迹，
+//!   每次都发送逐字节相同的包。
 
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Result, anyhow};
 use csv::ReaderBuilder;
-use log::{info, error};
+use log::{info, error, warn};
 use tauri::Emitter;
 
 use crate::{AppState, SendMessage, CsvLoopProgress};
 use crate::can_protocol::{create_can_send_packet_fixed, create_can_send_packet_variable};
-use crate::vehicle_control::extract_vehicle_control;
-
-/// 生成停止信号数据
-/// 格式：
-/// - 字节0-2：04 00 00（保持D档，速度为0）
-/// - 字节3-5：00 00 00（转向角为0）
-/// - 字节6：心跳值（最后一条数据的第7字节高位+1，最大F0，超过则回到00）
-/// - 字节7：校验位（前7字节的XOR）
-fn generate_stop_signal(last_can_data: &str) -> Result<String> {
-    let bytes: Vec<&str> = last_can_data.split_whitespace().collect();
-
-    if bytes.len() < 8 {
-        return Err(anyhow!("Invalid CAN data format for stop signal generation"));
-    }
-
-    // 获取第7字节（索引6）的高位作为心跳值
-    let byte7_str = bytes[6];
-    let byte7 = u8::from_str_radix(byte7_str, 16)
-        .map_err(|_| anyhow!("Failed to parse byte 7 as hex"))?;
-
-    // 心跳值 = 第7字节高位 + 1，最大值F0，超过则回到00
-    let heartbeat_high = (byte7 >> 4) + 1;
-    let heartbeat = if heartbeat_high > 0x0F { 0x00 } else { heartbeat_high };
-    let byte7_new = (heartbeat << 4) | 0x00; // 低位为0
-
-    // 停止信号：04 00 00 00 00 00 [heartbeat]0 [checksum]
-    let bytes_fixed = [0x04u8, 0x00, 0x00, 0x00, 0x00, 0x00];
-
-    // 计算校验位（前7字节的XOR）
-    let mut checksum = 0u8;
-    for &b in &bytes_fixed {
-        checksum ^= b;
-    }
-    checksum ^= byte7_new;
-
-    // 生成停止信号数据字符串
-    let stop_signal = format!(
-        "{:02X} {:02X} {:02X} {:02X} {:02X} {:02X} {:02X} {:02X}",
-        bytes_fixed[0], bytes_fixed[1], bytes_fixed[2], bytes_fixed[3],
-        bytes_fixed[4], bytes_fixed[5], byte7_new, checksum
-    );
-
-    info!("📤 [Rust] Generated stop signal: {} (heartbeat: {:X}, checksum: {:02X})", stop_signal, heartbeat, checksum);
+use crate::safety::SafetyController;
+use crate::vehicle_control::{extract_vehicle_control, VehicleControl};
+
+/// Stop CAN ID the CSV loops used to hardcode; still the default for
+/// [`SafetyController::from_config`] when a run's `config` doesn't override it.
+const DEFAULT_STOP_CAN_ID: &str = "0x18C4D2D0";
+
+/// 一条已经编译好的回放记录：发送包是现成的字节，`delay_after_ms` 是发完这一条之后、
+/// 发下一条之前要睡多久；是否跳过这次睡眠（只有整个回放最后一条记录才跳过）由
+/// `replay` 在热循环里用下标判断，记录本身不需要为此单独标一个"是不是最后一条"
+struct SequenceEntry {
+    packet: Vec<u8>,
+    can_id: String,
+    can_data: String,
+    vehicle_control: Option<VehicleControl>,
+    delay_after_ms: u64,
+}
 
-    Ok(stop_signal)
+/// [`prepare_csv_sequence`]/[`prepare_preloaded_sequence`] 编译出来的不透明回放句柄。
+///
+/// 所有字段都是私有的——`replay` 是这个类型唯一的消费者，调用方不需要也不应该关心
+/// 内部是怎么存的，就像一段已经 flush 好的 DMA 缓冲区，拿到手只管重放。
+///
+/// 停止帧不再跟着 handle 一起预编译：心跳计数器得在整个回放期间持续滚动（包括 watchdog
+/// 随时可能插进来发一帧），所以交给 [`SafetyController`] 在真正要停的那一刻现场生成。
+pub struct SequenceHandle {
+    entries: Vec<SequenceEntry>,
 }
 
-/// 运行 CSV 循环 - 从 CSV 内容读取数据并发送
-pub fn run_csv_loop(
-    csv_content: String,
+/// 一次性解析 CSV 内容，编译出可重复回放的 [`SequenceHandle`]
+pub fn prepare_csv_sequence(
+    csv_content: &str,
     interval_ms: u64,
     can_id_column_index: usize,
     can_data_column_index: usize,
     csv_start_row_index: usize,
-    config: serde_json::Value,
-    state: Arc<AppState>,
-) -> Result<()> {
-    info!("🔄 [Rust] run_csv_loop started - Start row: {}", csv_start_row_index);
+    config: &serde_json::Value,
+) -> Result<SequenceHandle> {
+    info!("🔧 [Rust] prepare_csv_sequence - Start row: {}", csv_start_row_index);
 
-    // Extract frame_type and protocol_length from config
-    // let frame_type = config.get("frame_type")
-    //     .and_then(|v| v.as_str())
-    //     .unwrap_or("extended")
-    //     .to_string();
     // todo 自动行驶先写死为extended，因为ID有四字节
     let frame_type = "extended";
-
     let protocol_length = config.get("protocol_length")
         .and_then(|v| v.as_str())
         .unwrap_or("fixed")
         .to_string();
 
-    // Parse CSV content from string
     let mut reader = ReaderBuilder::new()
         .has_headers(false)
         .from_reader(csv_content.as_bytes());
@@ -99,234 +83,201 @@ pub fn run_csv_loop(
     info!("✅ [Rust] Loaded {} records from CSV", records.len());
 
     if records.is_empty() {
-        info!("❌ [Rust] CSV file is empty");
         return Err(anyhow!("CSV file is empty"));
     }
 
-    // Check if start row index is valid
     if csv_start_row_index >= records.len() {
-        info!("❌ [Rust] Start row index {} out of range (max: {})", csv_start_row_index, records.len() - 1);
-        return Err(anyhow!("Start row index out of range"));
+        return Err(anyhow!(
+            "Start row index {} out of range (max: {})",
+            csv_start_row_index,
+            records.len() - 1
+        ));
     }
 
-    // Filter records starting from csv_start_row_index
     let filtered_records: Vec<_> = records.iter().skip(csv_start_row_index).collect();
-
     if filtered_records.is_empty() {
-        info!("❌ [Rust] No records after start row index");
         return Err(anyhow!("No records after start row index"));
     }
 
-    info!("✅ [Rust] Using {} records starting from row {}", filtered_records.len(), csv_start_row_index);
-
-    let mut last_can_data: Option<String> = None;
-    let mut user_stopped = false;
-
-    // Loop through records once
-    for (index, record) in filtered_records.iter().enumerate() {
-        // Check if loop should stop
-        if !state.csv_loop_running.load(Ordering::SeqCst) {
-            info!("🛑 [Rust] CSV loop stopped by user");
-            user_stopped = true;
-            break;
-        }
+    let mut entries = Vec::with_capacity(filtered_records.len());
 
-        // Get CAN ID and Data from specified columns
+    for record in filtered_records.iter() {
         let can_id = record
             .get(can_id_column_index)
             .ok_or_else(|| anyhow!("CAN ID column index out of range"))?
             .to_string();
-
         let can_data = record
             .get(can_data_column_index)
             .ok_or_else(|| anyhow!("CAN Data column index out of range"))?
             .to_string();
 
-        // Check if CAN data is empty - if so, stop the loop
+        // 空数据意味着录制的轨迹到此结束，和原来按行遍历时的提前 break 语义一致
         if can_data.trim().is_empty() {
-            info!("🛑 [Rust] Empty CAN data detected - CSV loop ended");
             break;
         }
 
-        // Try to parse vehicle control data (speed and steering angle)
         let vehicle_control = extract_vehicle_control(&can_data).ok();
-
-        if let Some(ref vc) = vehicle_control {
-            info!("Parsed vehicle control - Speed: {} mm/s, Steering: {:.3} rad",
-                  vc.linear_velocity_mms, vc.steering_angle);
-        }
-
-        // Create and send packet based on protocol_length
         let packet = if protocol_length == "variable" {
-            create_can_send_packet_variable(&can_id, &can_data, &frame_type)?
+            create_can_send_packet_variable(&can_id, &can_data, frame_type)?
         } else {
-            info!("Creating CAN send packet (fixed) - ID: {}, Data: {}, Type: {}", can_id, can_data, frame_type);
-            create_can_send_packet_fixed(&can_id, &can_data, &frame_type)?
+            create_can_send_packet_fixed(&can_id, &can_data, frame_type)?
         };
 
-        // Send packet through channel
-        {
-            let tx_send = state.tx_send.lock().unwrap();
-            if let Some(ref sender) = *tx_send {
-                if let Err(e) = sender.send(SendMessage { packet }) {
-                    error!("Failed to send packet through channel: {}", e);
-                } else {
-                    info!("Sent CAN message - ID: {}, Data: {}", can_id, can_data);
-                }
-            }
-        }
-
-        // Record the last CAN data for stop signal
-        last_can_data = Some(can_data.clone());
-
-        // Sleep for interval (except after the last record)
-        if index < filtered_records.len() - 1 {
-            thread::sleep(Duration::from_millis(interval_ms));
-        }
-    }
-
-    // Send stop signal if loop was stopped by user
-    if user_stopped {
-        if let Some(last_data) = last_can_data {
-            info!("📤 [Rust] Sending stop signal based on last data: {}", last_data);
-
-            // Generate stop signal
-            if let Ok(stop_signal_data) = generate_stop_signal(&last_data) {
-                // Send stop signal with CAN ID 0x18C4D2D0
-                let stop_can_id = "0x18C4D2D0";
-                let packet = if protocol_length == "variable" {
-                    create_can_send_packet_variable(stop_can_id, &stop_signal_data, &frame_type)?
-                } else {
-                    create_can_send_packet_fixed(stop_can_id, &stop_signal_data, &frame_type)?
-                };
-
-                // Send stop signal packet
-                {
-                    let tx_send = state.tx_send.lock().unwrap();
-                    if let Some(ref sender) = *tx_send {
-                        if let Err(e) = sender.send(SendMessage { packet }) {
-                            error!("Failed to send stop signal: {}", e);
-                        } else {
-                            info!("Sent stop signal - ID: {}, Data: {}", stop_can_id, stop_signal_data);
-                        }
-                    }
-                }
-            }
-        }
+        entries.push(SequenceEntry {
+            packet,
+            can_id,
+            can_data: can_data.clone(),
+            vehicle_control,
+            delay_after_ms: interval_ms,
+        });
     }
 
-    info!("✅ [Rust] CSV loop completed");
-
-    // Stop the loop flag
-    state.csv_loop_running.store(false, Ordering::SeqCst);
+    info!("✅ [Rust] Compiled {} entries into a SequenceHandle", entries.len());
 
-    Ok(())
+    Ok(SequenceHandle { entries })
 }
 
-/// 运行预加载 CSV 循环 - 从预加载的数据发送
-pub fn run_csv_loop_with_preloaded_data(
-    preloaded_data: Vec<CsvLoopProgress>,
+/// 从已经预加载好的 [`CsvLoopProgress`] 列表编译出 [`SequenceHandle`]；
+/// 和 [`prepare_csv_sequence`] 的区别只是输入已经是结构化数据，不用再解析 CSV
+pub fn prepare_preloaded_sequence(
+    preloaded_data: &[CsvLoopProgress],
     interval_ms: u64,
-    config: serde_json::Value,
-    state: Arc<AppState>,
-    app_handle: tauri::AppHandle,
-) -> Result<()> {
-    info!("🔄 [Rust] run_csv_loop_with_preloaded_data started - Records: {}", preloaded_data.len());
+    config: &serde_json::Value,
+) -> Result<SequenceHandle> {
+    info!("🔧 [Rust] prepare_preloaded_sequence - Records: {}", preloaded_data.len());
 
     let protocol_length = config.get("protocol_length")
         .and_then(|v| v.as_str())
-        .unwrap_or("fixed");
-
+        .unwrap_or("fixed")
+        .to_string();
     let frame_type = "extended"; // CSV driving data always uses extended frame
-    let mut last_can_data: Option<String> = None;
-    let mut user_stopped = false;
-
-    // Loop through records once
-    for (index, progress) in preloaded_data.iter().enumerate() {
-        // Check if loop should stop
-        if !state.csv_loop_running.load(Ordering::SeqCst) {
-            info!("🛑 [Rust] CSV loop stopped by user");
-            user_stopped = true;
-            break;
-        }
 
-        let can_id = &progress.can_id;
-        let can_data = &progress.can_data;
+    let mut entries = Vec::with_capacity(preloaded_data.len());
 
-        // Check if CAN data is empty - if so, stop the loop
+    for progress in preloaded_data.iter() {
+        let can_data = &progress.can_data;
         if can_data.trim().is_empty() {
-            info!("Empty CAN data detected - CSV loop ended");
             break;
         }
 
-        // Log vehicle control data if available
-        if let Some(ref vc) = progress.vehicle_control {
-            info!("🛞 Record {}/{} - Speed: {} mm/s, Steering: {:.2} degree",
-                  index + 1, preloaded_data.len(), vc.linear_velocity_mms, vc.steering_angle);
-        }
-
         let packet = if protocol_length == "variable" {
-            create_can_send_packet_variable(&can_id, &can_data, frame_type)?
+            create_can_send_packet_variable(&progress.can_id, can_data, frame_type)?
         } else {
-            create_can_send_packet_fixed(&can_id, &can_data, frame_type)?
+            create_can_send_packet_fixed(&progress.can_id, can_data, frame_type)?
         };
 
-        // Send packet through channel
-        {
-            let tx_send = state.tx_send.lock().unwrap();
-            if let Some(ref sender) = *tx_send {
-                if let Err(e) = sender.send(SendMessage { packet }) {
-                    error!("Failed to send packet through channel: {}", e);
-                } else {
-                    info!("Sent CAN message - ID: {}, Data: {}", can_id, can_data);
-                }
+        entries.push(SequenceEntry {
+            packet,
+            can_id: progress.can_id.clone(),
+            can_data: can_data.clone(),
+            vehicle_control: progress.vehicle_control.clone(),
+            delay_after_ms: interval_ms,
+        });
+    }
+
+    info!("✅ [Rust] Compiled {} entries into a SequenceHandle", entries.len());
+
+    Ok(SequenceHandle { entries })
+}
+
+/// CSV 回放用的 [`SafetyController`]，沿用旧版 `generate_stop_signal` 的停止 CAN ID
+/// 和扩展帧格式作为默认值，具体值仍然可以通过同一份 `config` 覆盖（见
+/// `SafetyController::from_config`）
+pub fn safety_controller_from_config(config: &serde_json::Value, protocol_length: &str) -> SafetyController {
+    SafetyController::from_config(config, DEFAULT_STOP_CAN_ID, "extended", protocol_length)
+}
+
+/// 时间敏感的回放热循环：只做 channel 发送和 `thread::sleep`，不解析 CSV、不构造发送包
+/// （每条记录仍会克隆一份包字节入队，并现场生成进度事件的 JSON），
+/// 可以把同一个 `handle` 传进来 `repeat_count` 次，每次都是逐字节相同的重放。
+///
+/// `repeat_count` 为 0 时不回放任何记录，但照常发停止帧并发出 `csv-loop-completed`。`safety` 统一管理收尾的停止帧：中途被
+/// `state.csv_loop_running` 打断、正常放完、或者 watchdog 发现发送通道卡住超过设定时限，
+/// 这三条路径现在都会发停止帧，不再只有用户手动停止才有（见 `safety.rs`）。
+pub fn replay(
+    handle: &SequenceHandle,
+    repeat_count: u32,
+    state: Arc<AppState>,
+    app_handle: tauri::AppHandle,
+    safety: SafetyController,
+) -> Result<()> {
+    info!("▶️ [Rust] replay started - {} entries x {} repeat(s)", handle.entries.len(), repeat_count);
+
+    safety.spawn_watchdog(state.csv_loop_running.clone(), state.clone(), app_handle.clone());
+
+    let total = handle.entries.len();
+
+    // Absolute-deadline scheduling: each entry's target send time is `start + target_elapsed`,
+    // where `target_elapsed` is the running sum of every `delay_after_ms` sent so far (carried
+    // across repeats, not reset per-repeat) — not `delay_after_ms`-sized sleeps stacked back to
+    // back, which would drift behind wall-clock by however long packet send takes each entry.
+    let start = Instant::now();
+    let mut target_elapsed = Duration::ZERO;
+    let mut previous_send_at = start;
+
+    'repeats: for rep in 0..repeat_count {
+        for (index, entry) in handle.entries.iter().enumerate() {
+            if !state.csv_loop_running.load(Ordering::SeqCst) {
+                info!("🛑 [Rust] Replay stopped by user");
+                break 'repeats;
             }
-        }
 
-        // Record the last CAN data for stop signal
-        last_can_data = Some(can_data.clone());
+            let now = Instant::now();
+            let achieved_period_ms = now.duration_since(previous_send_at).as_millis() as u64;
+            previous_send_at = now;
 
-        // Sleep for interval (except after the last record)
-        if index < preloaded_data.len() - 1 {
-            thread::sleep(Duration::from_millis(interval_ms));
-        }
-    }
+            if let Some(ref vc) = entry.vehicle_control {
+                info!("🛞 Repeat {} record {}/{} - Speed: {} mm/s, Steering: {:.2} degree",
+                      rep + 1, index + 1, total, vc.linear_velocity_mms, vc.steering_angle);
+            }
 
-    // Send stop signal if loop was stopped by user
-    if user_stopped {
-        if let Some(last_data) = last_can_data {
+            {
+                let tx_send = state.tx_send.lock().unwrap();
+                if let Some(ref queue) = *tx_send {
+                    if let Err(e) = queue.enqueue_blocking(SendMessage { packet: entry.packet.clone() }, Duration::from_millis(50)) {
+                        error!("Failed to send packet through channel: {}", e);
+                    } else {
+                        info!("Sent CAN message - ID: {}, Data: {}", entry.can_id, entry.can_data);
+                        safety.note_sent();
+                    }
+                }
+            }
 
-            // Generate stop signal
-            if let Ok(stop_signal_data) = generate_stop_signal(&last_data) {
-                // Send stop signal with CAN ID 0x18C4D2D0
-                let stop_can_id = "0x18C4D2D0";
-                let packet = if protocol_length == "variable" {
-                    create_can_send_packet_variable(stop_can_id, &stop_signal_data, frame_type)?
+            let _ = app_handle.emit("csv-loop-progress", serde_json::json!({
+                "index": index,
+                "total": total,
+                "can_id": entry.can_id,
+                "can_data": entry.can_data,
+                "vehicle_control": entry.vehicle_control,
+                "achieved_period_ms": achieved_period_ms,
+            }));
+
+            // 除了整个回放（所有重复次数）的最后一条记录，发完都按固定间隔睡一下；
+            // 这样重复回放之间也保持和记录内部同样的节奏，不会在循环衔接处抖一下
+            let is_very_last = rep + 1 == repeat_count && index + 1 == total;
+            if !is_very_last {
+                target_elapsed += Duration::from_millis(entry.delay_after_ms);
+                let deadline = start + target_elapsed;
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    warn!("[csv_loop] replay entry {} missed its deadline, running behind schedule", index);
                 } else {
-                    create_can_send_packet_fixed(stop_can_id, &stop_signal_data, frame_type)?
-                };
-
-                // Send stop signal packet
-                {
-                    let tx_send = state.tx_send.lock().unwrap();
-                    if let Some(ref sender) = *tx_send {
-                        if let Err(e) = sender.send(SendMessage { packet }) {
-                            error!("Failed to send stop signal: {}", e);
-                        } else {
-                            info!("Sent stop signal - ID: {}, Data: {}", stop_can_id, stop_signal_data);
-                        }
-                    }
+                    thread::sleep(remaining);
                 }
             }
         }
     }
 
-    info!("CSV loop completed");
+    // 不管是用户手动停止还是正常放完，都补发一次停止帧——watchdog 线程随时也可能已经
+    // 抢先发过一次（通道卡住的情况），多发一帧停止信号是安全的，heartbeat 会继续往下滚
+    if let Err(e) = safety.send_stop_frame(&state) {
+        error!("Failed to send stop signal: {}", e);
+    }
+
+    info!("✅ [Rust] Replay completed");
 
-    // Stop the loop flag
     state.csv_loop_running.store(false, Ordering::SeqCst);
 
-    // 发送 CSV 循环完成事件到前端
     let _ = app_handle.emit("csv-loop-completed", serde_json::json!({
         "status": "completed",
         "timestamp": chrono::Local::now().to_rfc3339(),
@@ -335,4 +286,3 @@ pub fn run_csv_loop_with_preloaded_data(
 
     Ok(())
 }
-
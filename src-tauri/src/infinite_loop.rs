@@ -1,18 +1,104 @@
 //! Infinite algorithmic driving loop
-//! Generates trajectory data in real-time without CSV files.
+//! Drives from a [`Trajectory`] - either the built-in demo scenario or one loaded live from
+//! JSON/CSV - closing the loop against real-time CAN feedback instead of replaying a CSV file.
 
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use log::{error, info};
-use tauri::Emitter;
+use anyhow::{Result, anyhow};
+use csv::ReaderBuilder;
+use log::{error, info, warn};
+use serde::Deserialize;
+use tauri::{Emitter, Listener};
 
 use crate::can_protocol::create_can_send_packet_fixed;
+use crate::safety::SafetyController;
+use crate::vehicle_control::extract_vehicle_control;
 use crate::{AppState, SendMessage};
 
+/// Discrete PID controller for one control axis (speed or steering).
+///
+/// Uses clamp-based anti-windup: the integral term only accumulates when the
+/// unclamped output is still inside `[output_min, output_max]`, or when the
+/// current error is already pulling the output back into range. Otherwise the
+/// output has saturated and letting the integral keep growing would just make
+/// it take longer to unwind once the error reverses.
+struct Pid {
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    output_min: f64,
+    output_max: f64,
+    integral: f64,
+    prev_error: f64,
+}
+
+impl Pid {
+    fn new(kp: f64, ki: f64, kd: f64, output_min: f64, output_max: f64) -> Self {
+        Self { kp, ki, kd, output_min, output_max, integral: 0.0, prev_error: 0.0 }
+    }
+
+    fn step(&mut self, target: f64, measured: f64, dt: f64) -> f64 {
+        let error = target - measured;
+        let derivative = (error - self.prev_error) / dt;
+        let tentative_integral = self.integral + error * dt;
+        let unclamped = self.kp * error + self.ki * tentative_integral + self.kd * derivative;
+        let output = unclamped.clamp(self.output_min, self.output_max);
+
+        let saturated_high = unclamped > output;
+        let saturated_low = unclamped < output;
+        if !(saturated_high || saturated_low) || (saturated_high && error < 0.0) || (saturated_low && error > 0.0) {
+            self.integral = tentative_integral;
+        }
+
+        self.prev_error = error;
+        output
+    }
+}
+
+/// Gains and feedback source for [`run_infinite_drive`]'s closed-loop controller,
+/// parsed out of the `config` the frontend passes to `start_infinite_drive`.
+/// Every field has a sane default so an empty `{}` config still runs open-loop
+/// until the first feedback frame arrives.
+struct FeedbackConfig {
+    feedback_can_id: String,
+    speed_kp: f64,
+    speed_ki: f64,
+    speed_kd: f64,
+    steering_kp: f64,
+    steering_ki: f64,
+    steering_kd: f64,
+}
+
+impl FeedbackConfig {
+    fn from_json(config: &serde_json::Value) -> Self {
+        let f64_field = |key: &str, default: f64| config.get(key).and_then(|v| v.as_f64()).unwrap_or(default);
+
+        Self {
+            feedback_can_id: config
+                .get("feedback_can_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("0x00000201")
+                .to_string(),
+            speed_kp: f64_field("speed_kp", 1.0),
+            speed_ki: f64_field("speed_ki", 0.05),
+            speed_kd: f64_field("speed_kd", 0.0),
+            steering_kp: f64_field("steering_kp", 1.0),
+            steering_ki: f64_field("steering_ki", 0.0),
+            steering_kd: f64_field("steering_kd", 0.05),
+        }
+    }
+}
+
+/// Speed output range (mm/s); mirrors the trajectory range [`Trajectory::default_keyframes`] ships
+const SPEED_OUTPUT_MIN: f64 = -3000.0;
+const SPEED_OUTPUT_MAX: f64 = 3000.0;
+/// Steering output range (degrees)
+const STEERING_OUTPUT_MIN: f64 = -30.0;
+const STEERING_OUTPUT_MAX: f64 = 30.0;
+
 /// Vehicle Control Data Structure
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct VehicleControl {
@@ -24,59 +110,239 @@ pub struct VehicleControl {
     pub gear_name: String,
 }
 
-/// Generate vehicle control data based on time
-/// Returns (speed_mm_s, steering_angle_deg)
-fn generate_control_data(elapsed_sec: f64) -> (i16, f64) {
-    // Complex Driving Scenario with Dynamic Speed
-    // Speed Range: 500 - 3000 mm/s
-    // Straight: Fast (~3000)
-    // Turns: Slow (~500-1500 depending on angle)
-
-    // Keyframes: (time, angle, speed)
-    let keyframes = [
-        (0.0, 0.0, 1500.0),   // Start slow
-        (5.0, 0.0, 3000.0),   // Accelerate on straight
-        (12.0, 10.0, 2000.0), // Slow down for turn
-        (22.0, 18.0, 2000.0), // Maintain speed in turn
-        (24.0, 12.0, 1800.0), // Slow more for tighter turn
-        (29.0, 5.0, 1600.0),
-        (31.0, 0.0, 1500.0), // Slowest for tightest part
-        (36.0, 0.0, 2000.0),
-        (39.0, -5.0, 2500.0), // Accelerate out of turn
-        (49.0, -2.0, 3000.0), // Max speed on straight
-        (52.0, 0.0, 1400.0),  // Slow down for sharp right turn
-        (62.0, 5.0, 1600.0),
-        (70.0, 15.0, 2500.0), // Accelerate slightly as turn widens
-        (84.0, 10.0, 2500.0),
-        (86.0, 5.0, 2000.0),
-        (100.0, 0.0, 1500.0),
-        (110.0, 0.0, 1500.0),
-    ];
-
-    let cycle_duration = 110.0;
-    let t = elapsed_sec % cycle_duration;
-
-    // Find current segment
-    let mut steering = 0.0;
-    let mut speed = 1000.0;
-
-    for i in 0..keyframes.len() - 1 {
-        let (t1, a1, s1) = keyframes[i];
-        let (t2, a2, s2) = keyframes[i + 1];
-
-        if t >= t1 && t < t2 {
-            // Interpolate
-            let progress = (t - t1) / (t2 - t1);
-            // Smooth ease-in-out
-            let ease = 0.5 * (1.0 - (progress * std::f64::consts::PI).cos());
-
-            steering = a1 + (a2 - a1) * ease;
-            speed = s1 + (s2 - s1) * ease;
-            break;
+/// How [`Trajectory::sample`] blends between two keyframes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interpolation {
+    /// `0.5 * (1 - cos(progress * PI))` - the original built-in demo's smooth ease-in-out.
+    Ease,
+    /// Straight linear blend between the two surrounding keyframes.
+    Linear,
+    /// Natural cubic spline solved once across every keyframe, for continuous curvature
+    /// (no slope discontinuities at keyframe boundaries) instead of a per-segment blend.
+    CubicSpline,
+}
+
+impl Interpolation {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "linear" => Interpolation::Linear,
+            "cubic_spline" | "spline" => Interpolation::CubicSpline,
+            _ => Interpolation::Ease,
+        }
+    }
+}
+
+/// One row of an externally-authored trajectory: a time offset into the cycle, plus the
+/// steering/speed targets at that point.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct TrajectoryRow {
+    t: f64,
+    steering_deg: f64,
+    speed_mms: f64,
+}
+
+/// A fully prepared, repeatedly-sampleable driving trajectory - the "handle" produced once by
+/// [`Trajectory::prepare_from_config`] from externally-authored keyframes (JSON or CSV) or, absent
+/// those, the original built-in demo scenario. [`Trajectory::sample`] never reparses or allocates;
+/// for [`Interpolation::CubicSpline`] the spline's second derivatives are solved once here too, not
+/// on every tick, matching the "compile once, replay many times" handle the CSV replay subsystem
+/// already uses.
+pub struct Trajectory {
+    ts: Vec<f64>,
+    steering_ys: Vec<f64>,
+    speed_ys: Vec<f64>,
+    cycle_duration: f64,
+    interpolation: Interpolation,
+    /// Empty unless `interpolation == CubicSpline`.
+    steering_spline_y2: Vec<f64>,
+    speed_spline_y2: Vec<f64>,
+}
+
+impl Trajectory {
+    fn default_keyframes() -> Vec<TrajectoryRow> {
+        // Complex Driving Scenario with Dynamic Speed
+        // Speed Range: 500 - 3000 mm/s
+        // Straight: Fast (~3000)
+        // Turns: Slow (~500-1500 depending on angle)
+        [
+            (0.0, 0.0, 1500.0),   // Start slow
+            (5.0, 0.0, 3000.0),   // Accelerate on straight
+            (12.0, 10.0, 2000.0), // Slow down for turn
+            (22.0, 18.0, 2000.0), // Maintain speed in turn
+            (24.0, 12.0, 1800.0), // Slow more for tighter turn
+            (29.0, 5.0, 1600.0),
+            (31.0, 0.0, 1500.0), // Slowest for tightest part
+            (36.0, 0.0, 2000.0),
+            (39.0, -5.0, 2500.0), // Accelerate out of turn
+            (49.0, -2.0, 3000.0), // Max speed on straight
+            (52.0, 0.0, 1400.0),  // Slow down for sharp right turn
+            (62.0, 5.0, 1600.0),
+            (70.0, 15.0, 2500.0), // Accelerate slightly as turn widens
+            (84.0, 10.0, 2500.0),
+            (86.0, 5.0, 2000.0),
+            (100.0, 0.0, 1500.0),
+            (110.0, 0.0, 1500.0),
+        ]
+        .into_iter()
+        .map(|(t, steering_deg, speed_mms)| TrajectoryRow { t, steering_deg, speed_mms })
+        .collect()
+    }
+
+    fn parse_csv(csv_content: &str) -> Result<Vec<TrajectoryRow>> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(csv_content.as_bytes());
+
+        let mut rows = Vec::new();
+        for result in reader.deserialize() {
+            let row: TrajectoryRow = result.map_err(|e| anyhow!("trajectory CSV read error: {}", e))?;
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Builds a handle from `rows`, solving the cubic-spline coefficients up front when
+    /// `interpolation` calls for them so the hot sampling loop stays allocation-free.
+    fn prepare(mut rows: Vec<TrajectoryRow>, cycle_duration: f64, interpolation: Interpolation) -> Result<Self> {
+        if rows.is_empty() {
+            return Err(anyhow!("trajectory must have at least one keyframe"));
+        }
+        rows.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+
+        let ts: Vec<f64> = rows.iter().map(|r| r.t).collect();
+        let steering_ys: Vec<f64> = rows.iter().map(|r| r.steering_deg).collect();
+        let speed_ys: Vec<f64> = rows.iter().map(|r| r.speed_mms).collect();
+
+        let (steering_spline_y2, speed_spline_y2) = if interpolation == Interpolation::CubicSpline {
+            (
+                natural_spline_second_derivatives(&ts, &steering_ys),
+                natural_spline_second_derivatives(&ts, &speed_ys),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        Ok(Self { ts, steering_ys, speed_ys, cycle_duration, interpolation, steering_spline_y2, speed_spline_y2 })
+    }
+
+    /// Loads the trajectory to drive, out of the same `config` blob [`FeedbackConfig`] reads its
+    /// PID gains from: `config.trajectory` (an array of `{t, steering_deg, speed_mms}` objects) or
+    /// `config.trajectory_csv` (the same rows as CSV text, header `t,steering_deg,speed_mms`), an
+    /// optional `config.interpolation` ("ease" | "linear" | "cubic_spline", default "ease"), and an
+    /// optional `config.cycle_duration_sec` override. With neither `trajectory` field set this falls
+    /// back to the original built-in demo scenario, so an empty `{}` config still drives exactly as
+    /// before. This is how the frontend switches scenarios live - just pass a different `config`.
+    pub(crate) fn prepare_from_config(config: &serde_json::Value) -> Result<Self> {
+        let interpolation = config
+            .get("interpolation")
+            .and_then(|v| v.as_str())
+            .map(Interpolation::from_name)
+            .unwrap_or(Interpolation::Ease);
+
+        let rows = if let Some(csv_content) = config.get("trajectory_csv").and_then(|v| v.as_str()) {
+            Self::parse_csv(csv_content)?
+        } else if let Some(json_rows) = config.get("trajectory") {
+            serde_json::from_value(json_rows.clone()).map_err(|e| anyhow!("invalid trajectory JSON: {}", e))?
+        } else {
+            Self::default_keyframes()
+        };
+
+        // Absent an explicit override, the cycle wraps right after the last authored keyframe -
+        // which for the built-in scenario is exactly the old hardcoded 110s cycle.
+        let cycle_duration = config
+            .get("cycle_duration_sec")
+            .and_then(|v| v.as_f64())
+            .unwrap_or_else(|| rows.iter().map(|r| r.t).fold(0.0, f64::max));
+
+        Self::prepare(rows, cycle_duration, interpolation)
+    }
+
+    /// Samples the trajectory at `elapsed_sec`, wrapping into the configured cycle.
+    /// Returns `(speed_mm_s, steering_angle_deg)`.
+    fn sample(&self, elapsed_sec: f64) -> (i16, f64) {
+        let t = elapsed_sec.rem_euclid(self.cycle_duration);
+
+        if self.interpolation == Interpolation::CubicSpline {
+            let steering = spline_eval(&self.ts, &self.steering_ys, &self.steering_spline_y2, t);
+            let speed = spline_eval(&self.ts, &self.speed_ys, &self.speed_spline_y2, t);
+            return (speed as i16, steering);
         }
+
+        let mut steering = *self.steering_ys.last().unwrap();
+        let mut speed = *self.speed_ys.last().unwrap();
+
+        for i in 0..self.ts.len().saturating_sub(1) {
+            let (t1, t2) = (self.ts[i], self.ts[i + 1]);
+            if t >= t1 && t < t2 {
+                let progress = (t - t1) / (t2 - t1);
+                let blend = match self.interpolation {
+                    Interpolation::Linear => progress,
+                    Interpolation::Ease => 0.5 * (1.0 - (progress * std::f64::consts::PI).cos()),
+                    Interpolation::CubicSpline => unreachable!(),
+                };
+                steering = self.steering_ys[i] + (self.steering_ys[i + 1] - self.steering_ys[i]) * blend;
+                speed = self.speed_ys[i] + (self.speed_ys[i + 1] - self.speed_ys[i]) * blend;
+                break;
+            }
+        }
+
+        (speed as i16, steering)
+    }
+}
+
+/// Natural cubic spline: solves for each keyframe's second derivative so [`spline_eval`] can
+/// interpolate with continuous curvature across the whole keyframe set, not just a per-segment
+/// blend. Standard tridiagonal solve with "natural" (zero second-derivative) boundary conditions.
+fn natural_spline_second_derivatives(xs: &[f64], ys: &[f64]) -> Vec<f64> {
+    let n = xs.len();
+    let mut y2 = vec![0.0; n];
+    if n < 3 {
+        return y2;
+    }
+    let mut u = vec![0.0; n];
+
+    for i in 1..n - 1 {
+        let sig = (xs[i] - xs[i - 1]) / (xs[i + 1] - xs[i - 1]);
+        let p = sig * y2[i - 1] + 2.0;
+        y2[i] = (sig - 1.0) / p;
+        let mut d = (ys[i + 1] - ys[i]) / (xs[i + 1] - xs[i]) - (ys[i] - ys[i - 1]) / (xs[i] - xs[i - 1]);
+        d = (6.0 * d / (xs[i + 1] - xs[i - 1]) - sig * u[i - 1]) / p;
+        u[i] = d;
     }
 
-    (speed as i16, steering)
+    for k in (0..n - 1).rev() {
+        y2[k] = y2[k] * y2[k + 1] + u[k];
+    }
+
+    y2
+}
+
+/// Evaluates the natural cubic spline defined by `(xs, ys, y2)` at `x`, where `y2` are the
+/// second derivatives [`natural_spline_second_derivatives`] solved for. `xs` must be sorted.
+fn spline_eval(xs: &[f64], ys: &[f64], y2: &[f64], x: f64) -> f64 {
+    let n = xs.len();
+    if n == 1 {
+        return ys[0];
+    }
+
+    let mut lo = 0;
+    let mut hi = n - 1;
+    while hi - lo > 1 {
+        let mid = (hi + lo) / 2;
+        if xs[mid] > x {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+
+    let h = xs[hi] - xs[lo];
+    if h == 0.0 {
+        return ys[lo];
+    }
+    let a = (xs[hi] - x) / h;
+    let b = (x - xs[lo]) / h;
+    a * ys[lo] + b * ys[hi] + ((a * a * a - a) * y2[lo] + (b * b * b - b) * y2[hi]) * (h * h) / 6.0
 }
 
 /// Create CAN data string from control values
@@ -93,14 +359,79 @@ fn create_can_data(speed: i16, steering: f64) -> String {
     )
 }
 
-pub fn run_infinite_drive(state: Arc<AppState>, app_handle: tauri::AppHandle) -> Result<()> {
+pub fn run_infinite_drive(
+    state: Arc<AppState>,
+    app_handle: tauri::AppHandle,
+    config: serde_json::Value,
+    trajectory: Trajectory,
+) -> Result<()> {
     info!("🚀 [Rust] Starting Infinite Algorithmic Drive");
 
+    let feedback_config = FeedbackConfig::from_json(&config);
+    let mut speed_pid = Pid::new(
+        feedback_config.speed_kp,
+        feedback_config.speed_ki,
+        feedback_config.speed_kd,
+        SPEED_OUTPUT_MIN,
+        SPEED_OUTPUT_MAX,
+    );
+    let mut steering_pid = Pid::new(
+        feedback_config.steering_kp,
+        feedback_config.steering_ki,
+        feedback_config.steering_kd,
+        STEERING_OUTPUT_MIN,
+        STEERING_OUTPUT_MAX,
+    );
+
+    // Latest measured (speed_mm_s, steering_deg) reported back by the vehicle, updated
+    // asynchronously by the listener below. `None` until the first feedback frame arrives.
+    let measured: Arc<Mutex<Option<(f64, f64)>>> = Arc::new(Mutex::new(None));
+    let feedback_id = feedback_config.feedback_can_id.clone();
+    let listener_measured = measured.clone();
+    let listener_running = state.auto_drive_running.clone();
+    let listener_id = app_handle.listen("can-message-received", move |event| {
+        if !listener_running.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(can_message) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+            return;
+        };
+        let Some(id_str) = can_message.get("id").and_then(|v| v.as_str()) else {
+            return;
+        };
+        if !id_str.eq_ignore_ascii_case(&feedback_id) {
+            return;
+        }
+        let Some(data_str) = can_message.get("data").and_then(|v| v.as_str()) else {
+            return;
+        };
+        if let Ok(control) = extract_vehicle_control(data_str) {
+            *listener_measured.lock().unwrap() =
+                Some((control.linear_velocity_mms as f64, control.steering_angle as f64));
+        }
+    });
+
     let start_time = Instant::now();
     let interval_ms = 20; // 50Hz update rate
+    let interval = Duration::from_millis(interval_ms);
+    let dt = interval_ms as f64 / 1000.0;
     let can_id = "0x200"; // Standard control ID
     let frame_type = "standard";
 
+    // Shared stop-frame/watchdog policy (see safety.rs) - stop CAN ID defaults to this same
+    // `0x200` control ID rather than the CSV loops' hardcoded `0x18C4D2D0`, so the heartbeat +
+    // checksum + watchdog guarantees apply here too, not just CSV replay.
+    let safety = SafetyController::from_config(&config, can_id, frame_type, "fixed");
+    safety.spawn_watchdog(state.auto_drive_running.clone(), state.clone(), app_handle.clone());
+
+    // Absolute-deadline scheduling: tick `n`'s target time is always `start_time +
+    // n * interval`, computed fresh off the wall clock instead of accumulating
+    // `interval_ms`-sized sleeps back to back. That keeps a long run's tick rate from
+    // drifting behind wall-clock just because packet creation/send takes a few
+    // microseconds of every period.
+    let mut tick: u32 = 0;
+    let mut previous_tick_at = start_time;
+
     // Loop until stopped
     loop {
         // Check stop condition
@@ -109,8 +440,20 @@ pub fn run_infinite_drive(state: Arc<AppState>, app_handle: tauri::AppHandle) ->
             break;
         }
 
+        let now = Instant::now();
+        let achieved_period_ms = now.duration_since(previous_tick_at).as_millis() as u64;
+        previous_tick_at = now;
+
         let elapsed = start_time.elapsed().as_secs_f64();
-        let (speed, steering) = generate_control_data(elapsed);
+        let (target_speed, target_steering) = trajectory.sample(elapsed);
+
+        // Until the first feedback frame arrives there is nothing to close the loop
+        // against, so fall back to the target itself (zero error, same as open-loop).
+        let (measured_speed, measured_steering) =
+            measured.lock().unwrap().unwrap_or((target_speed as f64, target_steering));
+
+        let speed = speed_pid.step(target_speed as f64, measured_speed, dt).round() as i16;
+        let steering = steering_pid.step(target_steering, measured_steering, dt);
         let can_data = create_can_data(speed, steering);
 
         // Create packet
@@ -118,9 +461,11 @@ pub fn run_infinite_drive(state: Arc<AppState>, app_handle: tauri::AppHandle) ->
             Ok(packet) => {
                 // Send packet
                 let tx_send = state.tx_send.lock().unwrap();
-                if let Some(ref sender) = *tx_send {
-                    if let Err(e) = sender.send(SendMessage { packet }) {
+                if let Some(ref queue) = *tx_send {
+                    if let Err(e) = queue.try_enqueue(SendMessage { packet }) {
                         error!("Failed to send packet: {}", e);
+                    } else {
+                        safety.note_sent();
                     }
                 }
             }
@@ -140,20 +485,27 @@ pub fn run_infinite_drive(state: Arc<AppState>, app_handle: tauri::AppHandle) ->
                 },
                 "can_id": can_id,
                 "can_data": can_data,
-                "interval_ms": interval_ms
+                "interval_ms": interval_ms,
+                "achieved_period_ms": achieved_period_ms,
             }),
         );
 
-        thread::sleep(Duration::from_millis(interval_ms));
+        tick += 1;
+        let deadline = start_time + interval * tick;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            warn!("[infinite_loop] tick {} missed its deadline, running behind schedule", tick);
+        } else {
+            thread::sleep(remaining);
+        }
     }
 
-    // Send stop signal (Speed 0, Steering 0)
-    let stop_data = create_can_data(0, 0.0);
-    if let Ok(packet) = create_can_send_packet_fixed(can_id, &stop_data, frame_type) {
-        let tx_send = state.tx_send.lock().unwrap();
-        if let Some(ref sender) = *tx_send {
-            let _ = sender.send(SendMessage { packet });
-        }
+    app_handle.unlisten(listener_id);
+
+    // Same shared stop frame the CSV loops send: user stop, normal completion, and a stalled
+    // send channel (the watchdog above) all end up here via `safety`.
+    if let Err(e) = safety.send_stop_frame(&state) {
+        error!("Failed to send stop signal: {}", e);
     }
 
     info!("✅ [Rust] Infinite drive ended");
@@ -1,153 +1,124 @@
 //! I/O 线程相关的函数
 //! 包括：串口读写、消息缓冲、事件发送等功能
 
-use std::sync::atomic::Ordering;
-use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
 
-use log::{error, info, warn};
+use log::info;
 use serialport::SerialPort;
 use tauri::Emitter;
 
 use crate::can_protocol::{
-    parse_distance_from_data, parse_received_can_message, parse_vehicle_status_8byte,
+    decode_j1939, parse_distance_from_data, parse_received_can_message, parse_vehicle_status_8byte,
 };
-use crate::{AppState, SendMessage};
-
-/// 启动 I/O 线程 - 独占拥有串口，处理读写
+use crate::framing::Framing;
+use crate::radar::{RadarAggregator, RADAR_OBJECT_GENERAL_ID, RADAR_STATUS_ID};
+use crate::reactor::Reactor;
+use crate::ring_buffer::RingBuffer;
+use crate::signal_db::SignalDatabase;
+use crate::{AppState, SendMessage, SerialConfig};
+
+/// 消息缓冲区容量，足够容纳多个帧，避免频繁触发"缓冲区过大"丢弃逻辑
+const MESSAGE_BUFFER_CAPACITY: usize = 1024;
+
+/// 将 CAN 串口注册到共享的 [`Reactor`] 事件循环上，返回注册得到的端口 id，
+/// 供断开连接时调用 `Reactor::remove_port` 使用
+///
+/// 不再为每路串口单独开一个"阻塞 read + 5ms 轮询睡眠"的线程：读写都在 Reactor
+/// 的事件循环线程里完成，帧重组逻辑通过闭包捕获状态后作为读回调传入。
+/// `framing` 由连接时的设置选出（见 [`crate::framing::from_name`]），决定这一路
+/// 串口用哪种方式在字节流里切分/校验一帧。`config` 原样保留给 `on_disconnect` 回调，
+/// 读写硬错误触发自动重连时要用它重新打开同一路串口（见 `reconnect.rs`）。`tx_depth`
+/// 是 `rx_send` 对应的有界发送队列的深度计数器（见 `tx_queue.rs`），Reactor 每从
+/// `rx_send` 取走一个包就递减一次。发送方向另外按同样的 `config.framing` 构造一份独立的
+/// `Framing` 实例用于 `encode_for_send`（见下方），和 `framing` 参数各自独立，互不影响。
 pub fn start_io_thread(
-    mut serial_port: Box<dyn SerialPort>,
+    serial_port: Box<dyn SerialPort>,
     state: AppState,
     rx_send: mpsc::Receiver<SendMessage>,
     app_handle: tauri::AppHandle,
-) {
+    framing: Box<dyn Framing>,
+    config: SerialConfig,
+    tx_depth: Arc<AtomicU64>,
+) -> u64 {
     state.write_thread_running.store(true, Ordering::SeqCst);
     state.receive_thread_running.store(true, Ordering::SeqCst);
 
-    thread::spawn(move || {
-        let mut buffer = vec![0u8; 1024];
-        let mut message_buffer = Vec::new(); // 消息缓冲区，用于组装完整的消息
-
-        // println!("🚀 [I/O Thread] Started - Ready to handle read/write operations");
-        // info!("🚀 [I/O Thread] Started - Ready to handle read/write operations");
-
-        while state.write_thread_running.load(Ordering::SeqCst) {
-            // 尝试接收写入请求（非阻塞）
-            match rx_send.try_recv() {
-                Ok(msg) => {
-                    // info!("I/O thread: sending {} bytes", msg.packet.len());
-                    match serial_port.write_all(&msg.packet) {
-                        Ok(_) => {
-                            if let Err(e) = serial_port.flush() {
-                                warn!("I/O thread: flush failed: {}", e);
-                            }
-                        }
-                        Err(e) => {
-                            error!("I/O thread: write failed: {}", e);
-                        }
-                    }
-                }
-                Err(mpsc::TryRecvError::Empty) => {
-                    // 没有写入请求，尝试读取
-                    match serial_port.read(&mut buffer) {
-                        Ok(n) if n > 0 => {
-                            let received_data = &buffer[..n];
-                            // println!("📥 [I/O Thread] Received {} bytes: {:02X?}", n, received_data);
-                            // info!("📥 [I/O Thread] Received {} bytes: {:02X?}", n, received_data);
-
-                            // 将接收到的数据添加到消息缓冲区
-                            message_buffer.extend_from_slice(received_data);
-                            // println!("📦 [I/O Thread] Message buffer size: {} bytes, content: {:02X?}", message_buffer.len(), message_buffer);
+    // 消息缓冲区，用于组装完整的消息；环形缓冲区避免每帧 drain 带来的内存搬移
+    let mut message_buffer = RingBuffer::with_capacity(MESSAGE_BUFFER_CAPACITY);
+    // 按测量周期聚合 ARS408 风格的雷达目标列表
+    let mut radar_aggregator = RadarAggregator::new();
+    let activity_state = state.clone();
+
+    // 发送方向独立持有一份同样配置的 Framing 实例，用来把待发送包编码成这种成帧
+    // 方式实际要写上线的字节（见 `framing.rs` 的 `encode_for_send`）；读写两份实例
+    // 各自维护自己的内部状态（如 COBS 的 CRC 失败计数），互不影响
+    let write_framing = crate::framing::from_name(&config.framing);
+    let write_encode = Box::new(move |packet: &[u8]| write_framing.encode_for_send(packet));
+
+    let disconnect_state = state.clone();
+    let disconnect_handle = app_handle.clone();
+    let on_disconnect = Box::new(move || {
+        crate::reconnect::trigger_reconnect(
+            disconnect_handle.clone(),
+            disconnect_state.clone(),
+            config.clone(),
+        );
+    });
 
-                            // 处理缓冲区中的完整消息
-                            process_message_buffer(&mut message_buffer, &app_handle);
-                        }
-                        Ok(_) => {
-                            // 读取0字节，短暂休眠
-                            thread::sleep(Duration::from_millis(5));
-                        }
-                        Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                            // 超时是正常的，继续循环
-                            continue;
-                        }
-                        Err(e) => {
-                            error!("I/O thread: read error: {}", e);
-                            println!("❌ [I/O Thread] Read error: {}", e);
-                            thread::sleep(Duration::from_millis(10));
-                        }
-                    }
-                }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    info!("I/O thread: channel disconnected, exiting");
-                    break;
-                }
-            }
+    let handler = Box::new(move |data: &[u8]| {
+        let enqueued = message_buffer.enqueue(data);
+        if enqueued < data.len() {
+            log::warn!(
+                "I/O thread: message buffer full, dropped {} bytes",
+                data.len() - enqueued
+            );
+        }
+        let got_frame = process_message_buffer(
+            &mut message_buffer,
+            framing.as_ref(),
+            &mut radar_aggregator,
+            &app_handle,
+        );
+        // 只有取出了完整帧才刷新活跃时间，链路心跳据此判断是否"假死"（见 reconnect.rs）
+        if got_frame {
+            *activity_state.last_activity.lock().unwrap() = std::time::Instant::now();
         }
-
-        state.receive_thread_running.store(false, Ordering::SeqCst);
-        info!("I/O thread stopped");
     });
-}
 
-/// 验证消息的校验和
-///
-/// 协议格式：固定20字节
-/// - 字节0-1: 消息头 (0xAA 0x55)
-/// - 字节2-18: 数据部分
-/// - 字节19: 校验和 (字节2-18的和的低8位)
-fn verify_checksum(message: &[u8]) -> bool {
-    if message.len() < 20 {
-        return false;
-    }
-
-    let checksum_received = message[19];
-    let checksum_calculated: u8 = message[2..19].iter().map(|&b| b as u32).sum::<u32>() as u8;
-
-    if checksum_received != checksum_calculated {
-        println!(
-            "❌ [Checksum] Mismatch - Received: 0x{:02X}, Calculated: 0x{:02X}",
-            checksum_received, checksum_calculated
-        );
-        return false;
-    }
+    let port_id = Reactor::global().add_port_with_options(
+        serial_port,
+        handler,
+        rx_send,
+        Some(state.cyclic_scheduler.clone()),
+        Some(on_disconnect),
+        Some(tx_depth),
+        Some(write_encode),
+    );
 
-    // println!("✅ [Checksum] Valid - 0x{:02X}", checksum_calculated);
-    true
+    info!("I/O: CAN port registered with reactor as port {}", port_id);
+    port_id
 }
 
-/// 在缓冲区中查找消息头 (AA 55)
-/// 返回消息头的位置，如果找到则清理前面的数据
-fn find_and_align_message_header(message_buffer: &mut Vec<u8>) -> bool {
-    if let Some(header_pos) = message_buffer.windows(2).position(|w| w == [0xAA, 0x55]) {
-        // println!("🎯 [I/O Thread] Found message header at position {}", header_pos);
+/// 查询信号数据库，将 `can_id`/`can_data` 字符串解码为结构化信号列表
+///
+/// `can_id` 形如 "0x00000123"，`can_data` 为空格分隔的十六进制字节（最多8字节）
+fn decode_signals(can_id: &str, can_data: &str) -> Option<Vec<crate::signal_db::DecodedSignal>> {
+    let id_hex = can_id
+        .strip_prefix("0x")
+        .or_else(|| can_id.strip_prefix("0X"))?;
+    let id = u32::from_str_radix(id_hex, 16).ok()?;
+
+    let mut data = [0u8; 8];
+    for (i, byte_str) in can_data.split_whitespace().enumerate().take(8) {
+        data[i] = u8::from_str_radix(byte_str, 16).ok()?;
+    }
 
-        if header_pos > 0 {
-            println!(
-                "⚠️  [I/O Thread] Discarding {} bytes before message header",
-                header_pos
-            );
-            message_buffer.drain(0..header_pos);
-        }
-        true
+    let signals = SignalDatabase::global().decode(id, &data);
+    if signals.is_empty() {
+        None
     } else {
-        // 没有找到完整的消息头，清理无效的字节
-        if message_buffer.len() < 2 {
-            // println!("⏳ [I/O Thread] Buffer too small to search for header: {} bytes", message_buffer.len());
-            return false;
-        }
-
-        if message_buffer[0] == 0xAA {
-            println!("⚠️  [I/O Thread] Found 0xAA at position 0, but next byte is 0x{:02X} (not 0x55), discarding", message_buffer[1]);
-            message_buffer.remove(0);
-        } else {
-            println!(
-                "⚠️  [I/O Thread] First byte is 0x{:02X} (not 0xAA), discarding",
-                message_buffer[0]
-            );
-            message_buffer.remove(0);
-        }
-        false
+        Some(signals)
     }
 }
 
@@ -158,6 +129,7 @@ fn handle_parsed_can_message(
     frame_type: &str,
     raw_hex: &str,
     timestamp: &str,
+    radar_aggregator: &mut RadarAggregator,
     app_handle: &tauri::AppHandle,
 ) {
     // println!("✅ [I/O Thread] Parsed CAN message - ID: {}, Data: {}", can_id, can_data);
@@ -186,6 +158,21 @@ fn handle_parsed_can_message(
         can_message["steeringAngle"] = serde_json::json!(steering_angle);
     }
 
+    // 通过信号数据库做表驱动解码，任意已注册 ID 都能得到结构化的 {name, value, unit} 列表
+    if let Some(signals) = decode_signals(can_id, can_data) {
+        can_message["signals"] = serde_json::json!(signals);
+    }
+
+    // 扩展帧按 J1939 拆出优先级/PGN/源地址/目标地址，标准帧没有这个字段
+    if let Some(j1939) = decode_j1939(can_id, frame_type) {
+        can_message["j1939"] = serde_json::json!({
+            "priority": j1939.priority,
+            "pgn": j1939.pgn,
+            "sourceAddress": j1939.source_address,
+            "destinationAddress": j1939.destination_address,
+        });
+    }
+
     let _ = app_handle.emit("can-message-received", can_message);
 
     // 检查是否是雷达消息
@@ -212,6 +199,36 @@ fn handle_parsed_can_message(
         });
         let _ = app_handle.emit("radar-message", radar_message);
     }
+
+    // ARS408 风格的目标列表：对象状态报文 + 逐目标 general 报文
+    if let Some((id, bytes)) = parse_can_id_and_bytes(can_id, can_data) {
+        if id == RADAR_OBJECT_GENERAL_ID {
+            radar_aggregator.handle_general(&bytes);
+        } else if id == RADAR_STATUS_ID {
+            if let Some((measurement_counter, objects)) = radar_aggregator.handle_status(&bytes) {
+                let radar_objects_message = serde_json::json!({
+                    "measurementCounter": measurement_counter,
+                    "objects": objects,
+                    "timestamp": timestamp,
+                });
+                let _ = app_handle.emit("radar-objects", radar_objects_message);
+            }
+        }
+    }
+}
+
+/// 将 "0xNNNNNNNN" 形式的 `can_id` 和空格分隔的十六进制 `can_data` 解析为数值 ID 与字节数组
+fn parse_can_id_and_bytes(can_id: &str, can_data: &str) -> Option<(u32, [u8; 8])> {
+    let id_hex = can_id
+        .strip_prefix("0x")
+        .or_else(|| can_id.strip_prefix("0X"))?;
+    let id = u32::from_str_radix(id_hex, 16).ok()?;
+
+    let mut data = [0u8; 8];
+    for (i, byte_str) in can_data.split_whitespace().enumerate().take(8) {
+        data[i] = u8::from_str_radix(byte_str, 16).ok()?;
+    }
+    Some((id, data))
 }
 
 /// 处理解析失败的消息
@@ -233,126 +250,39 @@ fn handle_parse_failure(raw_hex: &str, timestamp: &str, app_handle: &tauri::AppH
     let _ = app_handle.emit("can-message-received", can_message);
 }
 
-/// 处理消息缓冲区中的完整消息
+/// 处理消息缓冲区中已经就绪的完整帧
 ///
-/// 协议格式：固定20字节
-/// 处理 Windows 上消息被截断的情况（例如先发 0xAA，再发剩下的 19 字节）
-/// 改进逻辑：使用下一个 AA 55 作为分隔符，防止因校验和失败误删数据
-fn process_message_buffer(message_buffer: &mut Vec<u8>, app_handle: &tauri::AppHandle) {
-    loop {
-        // 第一步：查找并对齐消息头 (确保 buffer 以 AA 55 开头)
-        if !find_and_align_message_header(message_buffer) {
-            break;
-        }
-
-        // 第二步：查找下一个消息头 (AA 55)
-        // 从索引 2 开始查找 (跳过当前头的 AA 55)
-        let next_header_pos = message_buffer[2..]
-            .windows(2)
-            .position(|w| w == [0xAA, 0x55])
-            .map(|i| i + 2);
-
-        if let Some(pos) = next_header_pos {
-            // 情况 A: 找到了下一个消息头
-            // 当前包的范围是 [0..pos]
-            if pos == 20 {
-                // 长度正好是 20 字节，验证校验和
-                let candidate = &message_buffer[0..20];
-                if verify_checksum(candidate) {
-                    // 校验通过，处理消息
-                    let timestamp = chrono::Local::now().format("%H:%M:%S%.3f").to_string();
-                    let raw_hex = candidate
-                        .iter()
-                        .map(|b| format!("{:02X}", b))
-                        .collect::<Vec<_>>()
-                        .join(" ");
-
-                    if let Some((can_id, can_data, frame_type)) =
-                        parse_received_can_message(candidate)
-                    {
-                        handle_parsed_can_message(
-                            &can_id,
-                            &can_data,
-                            &frame_type,
-                            &raw_hex,
-                            &timestamp,
-                            app_handle,
-                        );
-                    } else {
-                        handle_parse_failure(&raw_hex, &timestamp, app_handle);
-                    }
-                } else {
-                    // 校验失败，但在 20 字节处发现了新头
-                    // 这意味着当前这 20 字节是损坏的，或者是偶然的 AA 55
-                    // 既然下一个头在正确的位置，我们丢弃当前的 20 字节，尝试处理下一个
-                    println!("⚠️  [I/O Thread] Checksum failed for aligned packet, discarding current packet");
-                }
-            } else {
-                // 长度不是 20 字节 (例如 19 字节就遇到了 AA 55)
-                // 说明当前包不完整或有错误，丢弃到下一个头的位置
-                println!("⚠️  [I/O Thread] Invalid packet length: {} (expected 20), discarding up to next header", pos);
-            }
-
-            // 无论处理成功与否，都移除当前包，移动到下一个头的位置
-            message_buffer.drain(0..pos);
+/// 帧边界查找/校验/payload 提取都交给 `framing` 负责（见 `framing.rs`），
+/// 这里只管把提取出来的 payload 交给协议内容解析。返回这一轮是否取出了至少一个完整帧。
+fn process_message_buffer(
+    message_buffer: &mut RingBuffer,
+    framing: &dyn Framing,
+    radar_aggregator: &mut RadarAggregator,
+    app_handle: &tauri::AppHandle,
+) -> bool {
+    let frames = crate::framing::drain_frames(framing, message_buffer);
+    let got_frame = !frames.is_empty();
+    for payload in frames {
+        let timestamp = chrono::Local::now().format("%H:%M:%S%.3f").to_string();
+        let raw_hex = payload
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if let Some((can_id, can_data, frame_type)) = parse_received_can_message(&payload) {
+            handle_parsed_can_message(
+                &can_id,
+                &can_data,
+                &frame_type,
+                &raw_hex,
+                &timestamp,
+                radar_aggregator,
+                app_handle,
+            );
         } else {
-            // 情况 B: 没有找到下一个消息头
-            // 我们需要判断是否已经有足够的数据来处理一个包
-            if message_buffer.len() >= 20 {
-                // 尝试验证前 20 字节
-                let candidate = &message_buffer[0..20];
-                if verify_checksum(candidate) {
-                    // 校验通过！这是一个有效的包 (虽然还没收到下一个头)
-                    // 处理它
-                    let timestamp = chrono::Local::now().format("%H:%M:%S%.3f").to_string();
-                    let raw_hex = candidate
-                        .iter()
-                        .map(|b| format!("{:02X}", b))
-                        .collect::<Vec<_>>()
-                        .join(" ");
-
-                    if let Some((can_id, can_data, frame_type)) =
-                        parse_received_can_message(candidate)
-                    {
-                        handle_parsed_can_message(
-                            &can_id,
-                            &can_data,
-                            &frame_type,
-                            &raw_hex,
-                            &timestamp,
-                            app_handle,
-                        );
-                    } else {
-                        handle_parse_failure(&raw_hex, &timestamp, app_handle);
-                    }
-
-                    // 移除已处理的 20 字节
-                    message_buffer.drain(0..20);
-                } else {
-                    // 校验失败，且后面没有发现 AA 55
-                    // 这可能是：
-                    // 1. 包还没收完 (虽然有20字节，但可能中间丢了数据，真正的头在后面还没来)
-                    // 2. 这是一个坏包
-                    //
-                    // 策略：等待更多数据 (不移除任何东西)，直到：
-                    // - 收到下一个 AA 55 (会进入 情况 A)
-                    // - 缓冲区过大 (防止内存泄漏)
-
-                    if message_buffer.len() > 200 {
-                        println!(
-                            "⚠️  [I/O Thread] Buffer too large ({}), discarding 1 byte to advance",
-                            message_buffer.len()
-                        );
-                        message_buffer.remove(0);
-                    } else {
-                        // 等待更多数据
-                        break;
-                    }
-                }
-            } else {
-                // 数据不足 20 字节，且没有下一个头 -> 等待更多数据
-                break;
-            }
+            handle_parse_failure(&raw_hex, &timestamp, app_handle);
         }
     }
+    got_frame
 }
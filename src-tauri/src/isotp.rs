@@ -0,0 +1,368 @@
+//! ISO-TP（ISO 15765-2）分段传输
+//!
+//! `create_can_send_packet_fixed`/`_variable` 喂给 CSV 循环/脚本控制台的都是假设
+//! 一帧能装下的单帧负载（经典 CAN 最多 8 字节），没法传诊断指令那种几十上百字节的
+//! 一次性负载。这个模块按 ISO 15765-2 把任意长度的负载拆成：
+//! - Single Frame（PCI 高 4 位 `0x0` + 低 4 位长度）：负载 <= 7 字节时一帧发完；
+//! - First Frame（PCI 高 4 位 `0x1` + 12 位总长度）+ Consecutive Frame（PCI 高 4 位
+//!   `0x2` + 4 位循环序号 0-15）：负载更长时先发 FF 带前 6 字节，再按 7 字节一段
+//!   发 CF，直到发完。
+//!
+//! ECU 收到 FF 后会回一帧 Flow Control（PCI 高 4 位 `0x3`）：低 4 位是
+//! ContinueToSend(0)/Wait(1)/Overflow(2)，后面跟 block size 和 STmin，发送方必须
+//! 按这个控制每个 block 发完后暂停等下一帧 FC、以及 CF 之间按 STmin 让出时间。
+//! 这里复用 `script_console.rs` 的 `wait_for_frame` 思路：通过监听已经在走的
+//! `"can-message-received"` 事件等 FC 回来，不需要单独开一路接收通道。
+//!
+//! 整个分段发送是阻塞的（要等 FC、要睡 STmin），所以 `send_isotp` 命令和
+//! `execute_script`/`start_infinite_drive` 一样，把实际工作丢到后台线程里跑，
+//! 通过 `"isotp-progress"`/`"isotp-completed"` 事件汇报进度，命令本身立刻返回。
+
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::warn;
+use tauri::{Emitter, Listener};
+
+use crate::can_frame::CanFrameBuilder;
+use crate::{AppState, SendMessage};
+
+/// ISO-TP 经典寻址下 First Frame 12 位长度字段能表示的最大负载长度
+pub const MAX_PAYLOAD_LEN: usize = 0x0FFF;
+
+/// 单帧能带的最大负载（PCI 占 1 字节）
+const SINGLE_FRAME_MAX_LEN: usize = 7;
+/// First Frame 的数据区长度（PCI 占 2 字节）
+const FIRST_FRAME_DATA_LEN: usize = 6;
+/// Consecutive Frame 的数据区长度（PCI 占 1 字节）
+const CONSECUTIVE_FRAME_DATA_LEN: usize = 7;
+
+const PCI_SINGLE_FRAME: u8 = 0x0;
+const PCI_FIRST_FRAME: u8 = 0x1;
+const PCI_CONSECUTIVE_FRAME: u8 = 0x2;
+const PCI_FLOW_CONTROL: u8 = 0x3;
+
+/// 等一帧 Flow Control 的超时；ECU 发 Wait 帧时会反复重置这个等待，但总重试次数
+/// 有上限（见 [`MAX_WAIT_FRAMES`]），避免一次传输永远卡住
+const FLOW_CONTROL_TIMEOUT: Duration = Duration::from_millis(1000);
+/// 连续收到多少次 Wait 帧就放弃，而不是无限等下去
+const MAX_WAIT_FRAMES: u32 = 16;
+
+/// [`send_isotp`] 能失败的具体原因
+#[derive(Debug)]
+pub enum Error {
+    /// CAN ID 字符串解析失败
+    InvalidCanId(String),
+    /// 负载长度超过 [`MAX_PAYLOAD_LEN`]（12 位长度字段装不下）
+    PayloadTooLarge { len: usize, max: usize },
+    /// 负载十六进制字符串解析失败
+    InvalidPayload(String),
+    /// 底层 CAN 帧构造/编码失败（CAN ID 超出范围等），透传原始错误
+    Frame(crate::can_frame::Error),
+    /// 发送队列已满或者还没连接
+    Send(String),
+    /// ECU 用 Flow Control 的 Overflow 状态要求放弃这次传输
+    Overflow,
+    /// 等待 Flow Control 帧超时（包括反复收到 Wait 帧超过 [`MAX_WAIT_FRAMES`] 次）
+    FlowControlTimeout,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidCanId(id) => write!(f, "invalid CAN ID: \"{}\"", id),
+            Error::PayloadTooLarge { len, max } => {
+                write!(f, "ISO-TP payload too large: {} bytes exceeds the {}-byte limit", len, max)
+            }
+            Error::InvalidPayload(e) => write!(f, "invalid ISO-TP payload: {}", e),
+            Error::Frame(e) => write!(f, "failed to build CAN frame: {}", e),
+            Error::Send(e) => write!(f, "failed to send frame: {}", e),
+            Error::Overflow => write!(f, "ECU reported Flow Control Overflow, aborting transfer"),
+            Error::FlowControlTimeout => write!(f, "timed out waiting for a Flow Control frame"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<crate::can_frame::Error> for Error {
+    fn from(e: crate::can_frame::Error) -> Self {
+        Error::Frame(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// 解析后的 Flow Control 帧
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FlowControl {
+    status: FlowStatus,
+    /// 一个 block 里能连续发几个 Consecutive Frame，`0` 表示不限制（一口气发完）
+    block_size: u8,
+    /// Consecutive Frame 之间至少要等多久，已经从 STmin 原始字节解码成 `Duration`
+    separation_time: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlowStatus {
+    ContinueToSend,
+    Wait,
+    Overflow,
+}
+
+impl FlowControl {
+    /// 从收到的 8 字节 CAN 数据里解析 Flow Control；不是 FC 帧（PCI 高 4 位不是
+    /// `0x3`）或者状态字段不认识时返回 `None`
+    fn parse(data: &[u8]) -> Option<FlowControl> {
+        let pci = *data.first()?;
+        if pci >> 4 != PCI_FLOW_CONTROL {
+            return None;
+        }
+        let status = match pci & 0x0F {
+            0 => FlowStatus::ContinueToSend,
+            1 => FlowStatus::Wait,
+            2 => FlowStatus::Overflow,
+            _ => return None,
+        };
+        let block_size = *data.get(1)?;
+        let st_min_raw = *data.get(2)?;
+        Some(FlowControl {
+            status,
+            block_size,
+            separation_time: decode_separation_time(st_min_raw),
+        })
+    }
+}
+
+/// STmin 字节 -> 实际间隔：0x00-0x7F 是 0-127 毫秒，0xF1-0xF9 是 100-900 微秒，
+/// 其它取值是协议保留值，按"不用额外等待"处理
+fn decode_separation_time(byte: u8) -> Duration {
+    match byte {
+        0x00..=0x7F => Duration::from_millis(byte as u64),
+        0xF1..=0xF9 => Duration::from_micros((byte as u64 - 0xF0) * 100),
+        _ => Duration::from_millis(0),
+    }
+}
+
+/// 解析一个可选 "0x"/"0X" 前缀的 CAN ID 字符串；是否为扩展帧由数值是否超过
+/// 11 位范围自动推断（[`send_isotp`] 的命令只接受一个 CAN ID，不像
+/// `send_can_message` 那样额外带一个 `frame_type` 参数）
+fn parse_can_id(id: &str) -> Result<u32> {
+    let hex_part = id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")).unwrap_or(id);
+    u32::from_str_radix(hex_part, 16).map_err(|_| Error::InvalidCanId(id.to_string()))
+}
+
+fn enqueue(state: &AppState, packet: Vec<u8>) -> Result<()> {
+    let tx_send = state.tx_send.lock().unwrap();
+    match *tx_send {
+        Some(ref queue) => queue.try_enqueue(SendMessage { packet }).map_err(|e| Error::Send(e.to_string())),
+        None => Err(Error::Send("send channel not available".to_string())),
+    }
+}
+
+fn send_frame(state: &AppState, can_id: u32, extended: bool, data: Vec<u8>) -> Result<()> {
+    let frame = CanFrameBuilder::new(can_id).extended(extended).data(data).build()?;
+    enqueue(state, frame.to_fixed_packet()?)
+}
+
+/// 监听一次性的 `"can-message-received"` 事件，直到收到来自 `can_id` 的 Flow
+/// Control 帧，或者 `timeout` 用尽；和 `script_console.rs` 的 `wait_for_frame`
+/// 是同一个套路，只是这里还要把帧内容解析成 [`FlowControl`] 而不只是判断 ID 匹配
+fn wait_for_flow_control(app_handle: &tauri::AppHandle, can_id: u32, timeout: Duration) -> Result<FlowControl> {
+    let (tx, rx) = mpsc::channel::<FlowControl>();
+    let expected = format!("0x{:08X}", can_id);
+
+    let listener_id = app_handle.listen("can-message-received", move |event| {
+        let Ok(can_message) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+            return;
+        };
+        let Some(id_str) = can_message.get("id").and_then(|v| v.as_str()) else {
+            return;
+        };
+        if !id_str.eq_ignore_ascii_case(&expected) {
+            return;
+        }
+        let Some(data_str) = can_message.get("data").and_then(|v| v.as_str()) else {
+            return;
+        };
+        let Ok(bytes) = crate::can_protocol::parse_hex_data_bytes(data_str) else {
+            return;
+        };
+        if let Some(fc) = FlowControl::parse(&bytes) {
+            let _ = tx.send(fc);
+        }
+    });
+
+    let result = rx.recv_timeout(timeout).map_err(|_| Error::FlowControlTimeout);
+    app_handle.unlisten(listener_id);
+    result
+}
+
+/// 等到一帧 ContinueToSend；中途收到 Wait 就继续等下一帧（最多等
+/// [`MAX_WAIT_FRAMES`] 次），收到 Overflow 就放弃整次传输
+fn wait_for_continue_to_send(app_handle: &tauri::AppHandle, can_id: u32) -> Result<FlowControl> {
+    for _ in 0..MAX_WAIT_FRAMES {
+        let fc = wait_for_flow_control(app_handle, can_id, FLOW_CONTROL_TIMEOUT)?;
+        match fc.status {
+            FlowStatus::ContinueToSend => return Ok(fc),
+            FlowStatus::Overflow => return Err(Error::Overflow),
+            FlowStatus::Wait => {
+                warn!("ISO-TP: ECU sent Wait flow control for 0x{:08X}, extending wait", can_id);
+                continue;
+            }
+        }
+    }
+    Err(Error::FlowControlTimeout)
+}
+
+fn emit_progress(app_handle: &tauri::AppHandle, frame_kind: &str, frame_index: usize) {
+    let _ = app_handle.emit(
+        "isotp-progress",
+        serde_json::json!({ "frame": frame_kind, "index": frame_index }),
+    );
+}
+
+/// 把 `payload` 按 ISO-TP 分段发送到 `can_id`；长度 <= 7 字节时发一个 Single
+/// Frame 直接返回，否则发 First Frame，等 ECU 回 Flow Control 同意后按
+/// block size/STmin 发完剩下的 Consecutive Frame。
+///
+/// 这是阻塞调用（要等 FC、要睡 STmin），调用方（[`crate::commands::send_isotp`]）
+/// 把它丢到后台线程里跑，通过 `"isotp-progress"`/`"isotp-completed"` 事件汇报进度
+pub fn send_isotp(can_id: u32, payload: Vec<u8>, state: AppState, app_handle: tauri::AppHandle) -> Result<()> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        let err = Error::PayloadTooLarge { len: payload.len(), max: MAX_PAYLOAD_LEN };
+        let _ = app_handle.emit(
+            "isotp-completed",
+            serde_json::json!({ "status": "error", "detail": err.to_string() }),
+        );
+        return Err(err);
+    }
+
+    let extended = can_id > 0x7FF;
+    let result = send_isotp_inner(can_id, extended, &payload, &state, &app_handle);
+
+    let _ = app_handle.emit(
+        "isotp-completed",
+        match &result {
+            Ok(()) => serde_json::json!({ "status": "ok" }),
+            Err(e) => serde_json::json!({ "status": "error", "detail": e.to_string() }),
+        },
+    );
+
+    result
+}
+
+fn send_isotp_inner(
+    can_id: u32,
+    extended: bool,
+    payload: &[u8],
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+) -> Result<()> {
+    if payload.len() <= SINGLE_FRAME_MAX_LEN {
+        let mut data = vec![PCI_SINGLE_FRAME << 4 | payload.len() as u8];
+        data.extend_from_slice(payload);
+        send_frame(state, can_id, extended, data)?;
+        emit_progress(app_handle, "single", 0);
+        return Ok(());
+    }
+
+    let total_len = payload.len() as u16;
+    let mut data = vec![PCI_FIRST_FRAME << 4 | ((total_len >> 8) & 0x0F) as u8, (total_len & 0xFF) as u8];
+    data.extend_from_slice(&payload[..FIRST_FRAME_DATA_LEN]);
+    send_frame(state, can_id, extended, data)?;
+    emit_progress(app_handle, "first", 0);
+
+    let mut fc = wait_for_continue_to_send(app_handle, can_id)?;
+
+    let remaining = &payload[FIRST_FRAME_DATA_LEN..];
+    let chunks: Vec<&[u8]> = remaining.chunks(CONSECUTIVE_FRAME_DATA_LEN).collect();
+    let mut seq: u8 = 1;
+    let mut sent_in_block: u32 = 0;
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let mut data = vec![PCI_CONSECUTIVE_FRAME << 4 | seq];
+        data.extend_from_slice(chunk);
+        send_frame(state, can_id, extended, data)?;
+        emit_progress(app_handle, "consecutive", index + 1);
+
+        seq = if seq == 15 { 0 } else { seq + 1 };
+        sent_in_block += 1;
+
+        let is_last = index + 1 == chunks.len();
+        if is_last {
+            break;
+        }
+
+        if fc.block_size != 0 && sent_in_block >= fc.block_size as u32 {
+            fc = wait_for_continue_to_send(app_handle, can_id)?;
+            sent_in_block = 0;
+        } else if !fc.separation_time.is_zero() {
+            std::thread::sleep(fc.separation_time);
+        }
+    }
+
+    Ok(())
+}
+
+/// 解析 `send_isotp` 命令传进来的 `can_id` 字符串和负载十六进制字符串；
+/// 失败时返回的 [`Error`] 由调用方转成字符串交给前端
+pub fn parse_send_isotp_args(can_id: &str, payload: &str) -> Result<(u32, Vec<u8>)> {
+    let can_id = parse_can_id(can_id)?;
+    let payload = crate::can_protocol::parse_hex_data_bytes(payload).map_err(|e| Error::InvalidPayload(e.to_string()))?;
+    Ok((can_id, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_separation_time_in_milliseconds_range() {
+        assert_eq!(decode_separation_time(0x00), Duration::from_millis(0));
+        assert_eq!(decode_separation_time(0x7F), Duration::from_millis(127));
+    }
+
+    #[test]
+    fn decodes_separation_time_in_microseconds_range() {
+        assert_eq!(decode_separation_time(0xF1), Duration::from_micros(100));
+        assert_eq!(decode_separation_time(0xF9), Duration::from_micros(900));
+    }
+
+    #[test]
+    fn decodes_reserved_separation_time_as_zero() {
+        assert_eq!(decode_separation_time(0x80), Duration::from_millis(0));
+        assert_eq!(decode_separation_time(0xFA), Duration::from_millis(0));
+    }
+
+    #[test]
+    fn flow_control_parses_continue_to_send() {
+        let fc = FlowControl::parse(&[0x30, 0x08, 0x0A]).unwrap();
+        assert_eq!(fc.status, FlowStatus::ContinueToSend);
+        assert_eq!(fc.block_size, 0x08);
+        assert_eq!(fc.separation_time, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn flow_control_parses_wait_and_overflow() {
+        assert_eq!(FlowControl::parse(&[0x31, 0x00, 0x00]).unwrap().status, FlowStatus::Wait);
+        assert_eq!(FlowControl::parse(&[0x32, 0x00, 0x00]).unwrap().status, FlowStatus::Overflow);
+    }
+
+    #[test]
+    fn flow_control_rejects_non_fc_pci() {
+        assert!(FlowControl::parse(&[0x10, 0x08, 0x00]).is_none());
+    }
+
+    #[test]
+    fn parse_can_id_accepts_optional_0x_prefix() {
+        assert_eq!(parse_can_id("0x123").unwrap(), 0x123);
+        assert_eq!(parse_can_id("123").unwrap(), 0x123);
+    }
+
+    #[test]
+    fn parse_can_id_rejects_invalid_hex() {
+        assert!(matches!(parse_can_id("zz").unwrap_err(), Error::InvalidCanId(_)));
+    }
+}
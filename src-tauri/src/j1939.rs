@@ -0,0 +1,155 @@
+//! J1939 扩展帧 ID 解码/编码
+//!
+//! SAE J1939（重型车辆/农机总线）把 29 位扩展 CAN ID 的各个位域赋予了固定含义，
+//! 和这里其它协议把扩展 ID 当作不透明 29 位数字直接收发不同。这个模块只管
+//! ID 本身的位域拆装，不碰数据负载；`parse_received_can_message` 之外的调用方
+//! 可以按需调用 [`J1939Id::from_raw`] 把已经解析出来的 `can_id` 再拆一层。
+//!
+//! 29 位布局（从高到低）：
+//! - priority: bit 28-26（3 位）
+//! - EDP（extended data page）: bit 25（1 位）
+//! - DP（data page）: bit 24（1 位）
+//! - PF（PDU Format）: bit 23-16（8 位）
+//! - PS（PDU Specific）: bit 15-8（8 位）
+//! - SA（source address）: bit 7-0（8 位）
+//!
+//! PGN 的算法按 PF 是否 < 240 分两种：
+//! - PF < 240（PDU1，点对点）：PGN = (DP << 16) | (PF << 8)，PS 是目标地址
+//! - PF >= 240（PDU2，广播）：PGN = (DP << 16) | (PF << 8) | PS，没有单独的目标地址
+
+/// 从 29 位扩展 CAN ID 拆出来的 J1939 字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct J1939Id {
+    pub priority: u8,
+    pub pgn: u32,
+    pub source_address: u8,
+    pub destination_address: Option<u8>,
+}
+
+impl J1939Id {
+    /// 把一个 29 位扩展 CAN ID（高 3 位保留/未用）拆成 J1939 字段
+    pub fn from_raw(id: u32) -> J1939Id {
+        let priority = ((id >> 26) & 0x7) as u8;
+        let dp = ((id >> 24) & 0x1) as u8;
+        let pf = ((id >> 16) & 0xFF) as u8;
+        let ps = ((id >> 8) & 0xFF) as u8;
+        let source_address = (id & 0xFF) as u8;
+
+        // EDP 不计入 PGN（见模块文档的算法），只有 DP 占 bit 16
+        let data_page = (dp as u32) << 16;
+
+        if pf < 240 {
+            // PDU1：点对点，PS 是目标地址，不计入 PGN
+            J1939Id {
+                priority,
+                pgn: data_page | ((pf as u32) << 8),
+                source_address,
+                destination_address: Some(ps),
+            }
+        } else {
+            // PDU2：广播，PS 并入 PGN，没有单独的目标地址
+            J1939Id {
+                priority,
+                pgn: data_page | ((pf as u32) << 8) | (ps as u32),
+                source_address,
+                destination_address: None,
+            }
+        }
+    }
+
+    /// 把 J1939 字段重新组装回 29 位扩展 CAN ID
+    ///
+    /// `pgn` 的 PF（`(pgn >> 8) & 0xFF`）决定走 PDU1 还是 PDU2：PDU1 时用
+    /// `destination_address`（缺省按广播地址 0xFF 处理），PDU2 时 PS 取自 `pgn` 本身，
+    /// 忽略 `destination_address`。
+    pub fn to_raw(&self) -> u32 {
+        let pf = ((self.pgn >> 8) & 0xFF) as u8;
+        // DP 位在 `pgn` 里是 bit 16，但在 29 位 ID 里是 bit 24，要再往左移 8 位，
+        // 不然会和下面的 PF（bit 16-23）撞在一起；EDP 不在 PGN 里，写回时恒为 0
+        let data_page = (self.pgn & 0x10000) << 8;
+
+        let ps = if pf < 240 {
+            self.destination_address.unwrap_or(0xFF)
+        } else {
+            (self.pgn & 0xFF) as u8
+        };
+
+        ((self.priority as u32) << 26)
+            | data_page
+            | ((pf as u32) << 16)
+            | ((ps as u32) << 8)
+            | (self.source_address as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_pdu1_point_to_point_id() {
+        // priority=3, EDP=0, DP=0, PF=0xEA (234, < 240 -> PDU1), PS=0x05 (dest), SA=0x21
+        let raw = (3u32 << 26) | (0xEA << 16) | (0x05 << 8) | 0x21;
+        let j1939 = J1939Id::from_raw(raw);
+
+        assert_eq!(j1939.priority, 3);
+        assert_eq!(j1939.pgn, 0xEA00);
+        assert_eq!(j1939.source_address, 0x21);
+        assert_eq!(j1939.destination_address, Some(0x05));
+    }
+
+    #[test]
+    fn decodes_pdu2_broadcast_id() {
+        // priority=6, EDP=0, DP=0, PF=0xFE (254, >= 240 -> PDU2), PS=0xF0, SA=0x80
+        let raw = (6u32 << 26) | (0xFE << 16) | (0xF0 << 8) | 0x80;
+        let j1939 = J1939Id::from_raw(raw);
+
+        assert_eq!(j1939.priority, 6);
+        assert_eq!(j1939.pgn, 0xFEF0);
+        assert_eq!(j1939.source_address, 0x80);
+        assert_eq!(j1939.destination_address, None);
+    }
+
+    #[test]
+    fn round_trips_pdu1_through_to_raw() {
+        let j1939 = J1939Id {
+            priority: 3,
+            pgn: 0xEA00,
+            source_address: 0x21,
+            destination_address: Some(0x05),
+        };
+        assert_eq!(J1939Id::from_raw(j1939.to_raw()), j1939);
+    }
+
+    #[test]
+    fn round_trips_pdu2_through_to_raw() {
+        let j1939 = J1939Id {
+            priority: 6,
+            pgn: 0xFEF0,
+            source_address: 0x80,
+            destination_address: None,
+        };
+        assert_eq!(J1939Id::from_raw(j1939.to_raw()), j1939);
+    }
+
+    #[test]
+    fn round_trips_data_page_1_through_to_raw() {
+        // DP=1 PDU2: priority=0, EDP=0, DP=1, PF=0xFF (>= 240 -> PDU2), PS=0x10, SA=0x00
+        let raw = (1u32 << 24) | (0xFF << 16) | (0x10 << 8) | 0x00;
+        let j1939 = J1939Id::from_raw(raw);
+        assert_eq!(j1939.pgn, 0x1FF10);
+
+        // to_raw must put the DP bit back at bit 24, not leave it colliding with PF
+        assert_eq!(j1939.to_raw(), raw);
+        assert_eq!(J1939Id::from_raw(j1939.to_raw()), j1939);
+    }
+
+    #[test]
+    fn honors_data_page_bits() {
+        // DP=1 -> PGN should carry bit 16
+        let raw = (0u32 << 26) | (1 << 24) | (0xFF << 16) | (0x10 << 8) | 0x00;
+        let j1939 = J1939Id::from_raw(raw);
+        assert_eq!(j1939.pgn, 0x1FF10);
+        assert_eq!(j1939.destination_address, None);
+    }
+}
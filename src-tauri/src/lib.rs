@@ -1,24 +1,65 @@
 use std::sync::atomic::AtomicBool;
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use serde::{Deserialize, Serialize};
 
 mod vehicle_control;
 use vehicle_control::VehicleControl;
 
+mod can_frame;
 mod can_protocol;
 
 mod csv_loop;
 
+mod j1939;
+
+mod framing;
+mod infinite_loop;
 mod io_thread;
+mod isotp;
+#[cfg(feature = "mqtt")]
+mod mqtt_bridge;
+mod port_monitor;
+mod radar;
+mod reactor;
+mod reconnect;
+mod replay_log;
+mod ring_buffer;
+mod safety;
+mod scheduler;
+mod script_console;
+mod signal_db;
+mod slcan;
 mod system_monitor_thread;
+mod tx_queue;
 
 mod commands;
 use commands::{
-    close_system_monitor_window, connect_serial, connect_system_monitor, disconnect_serial,
-    disconnect_system_monitor, get_available_ports, open_system_monitor_window, preload_csv_data,
-    send_can_message, start_csv_loop, start_csv_loop_with_preloaded_data, stop_csv_loop,
+    cancel_cyclic_message, close_system_monitor_window, connect_mqtt_bridge, connect_serial,
+    connect_system_monitor, disconnect_mqtt_bridge, disconnect_serial, disconnect_system_monitor,
+    execute_script, get_available_ports, get_tx_queue_stats, list_cyclic_messages,
+    open_system_monitor_window, preload_csv_data, schedule_cyclic_message, send_can_message,
+    send_isotp, start_csv_loop, start_csv_loop_with_preloaded_data, start_infinite_drive,
+    stop_csv_loop, stop_infinite_drive, stop_script,
 };
+use scheduler::TimerWheel;
+
+fn default_framing() -> String {
+    "sum8header".to_string()
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_tx_queue_size() -> usize {
+    256
+}
+
+fn default_data_baud_rate() -> u32 {
+    2_000_000
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerialConfig {
@@ -28,6 +69,28 @@ pub struct SerialConfig {
     frame_type: String,
     protocol_length: String,
     can_mode: String,
+    // CAN FD 使能开关，默认关闭（经典 CAN 2.0），开启后适配器按 can_baud_rate 跑仲裁段、
+    // 按 data_baud_rate 跑数据段（见 can_protocol.rs 的 create_can_send_packet_*_fd）
+    #[serde(default)]
+    can_fd_enabled: bool,
+    // CAN FD 数据段波特率（BRS 切换后的速率），默认 2Mbps；未开启 can_fd_enabled 时忽略
+    #[serde(default = "default_data_baud_rate")]
+    data_baud_rate: u32,
+    // 连接时选择的成帧方式："sum8header"（默认）、"crc16" 或 "cobs"；
+    // "cobs" 还会在发送方向给每个包追加 CRC-16 再做 COBS 转义（见 framing.rs 的
+    // encode_for_send），读写两端都能在丢字节/位翻转后自动重新同步或探测出来。
+    // 旧版前端不传这个字段时按 serde(default) 回退到 sum8header 保持兼容
+    #[serde(default = "default_framing")]
+    framing: String,
+    // 断线后是否自动重连（指数退避）+ 链路心跳，默认关闭以保持旧版前端的行为
+    #[serde(default)]
+    reconnect: bool,
+    // 重连指数退避的上限（毫秒），默认 10 秒
+    #[serde(default = "default_max_backoff_ms")]
+    max_backoff_ms: u64,
+    // 有界发送队列的容量，默认 256 个待发送包，超过就是背压（见 tx_queue.rs）
+    #[serde(default = "default_tx_queue_size")]
+    tx_queue_size: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,16 +120,43 @@ pub struct SendMessage {
 // Application state
 #[derive(Clone)]
 pub struct AppState {
-    // 发送通道的发送端 - 用于将数据发送到写入线程
-    tx_send: Arc<Mutex<Option<mpsc::Sender<SendMessage>>>>,
+    // 发送通道的发送端 - 用于将数据发送到写入线程；有界队列，满了会显式报错/触发背压
+    tx_send: Arc<Mutex<Option<tx_queue::TxQueue>>>,
     is_connected: Arc<Mutex<bool>>,
     csv_loop_running: Arc<AtomicBool>,
     receive_thread_running: Arc<AtomicBool>,
     write_thread_running: Arc<AtomicBool>,
+    // 无限算法行驶循环的运行标志（见 infinite_loop.rs）
+    pub auto_drive_running: Arc<AtomicBool>,
 
     // System Monitor State
     pub system_monitor_connected: Arc<Mutex<bool>>,
     pub system_monitor_thread_running: Arc<AtomicBool>,
+
+    // 周期发送任务的定时轮，由 Reactor 的事件循环驱动
+    pub cyclic_scheduler: Arc<Mutex<TimerWheel>>,
+
+    // 注册到共享 Reactor 上的端口 id，断开连接时用来注销
+    pub io_port_id: Arc<Mutex<Option<u64>>>,
+    pub system_monitor_port_id: Arc<Mutex<Option<u64>>>,
+
+    // 串口热插拔监控线程的运行标志
+    pub port_monitor_running: Arc<AtomicBool>,
+    // 当前已连接的串口名，供热插拔监控判断 "active-port-lost"
+    pub active_port: Arc<Mutex<Option<String>>>,
+
+    // 最近一次成功收到完整帧的时间，供链路心跳判断是否失活（见 reconnect.rs）
+    pub last_activity: Arc<Mutex<Instant>>,
+    // 自动重连退避循环的运行标志，避免硬件错误和心跳超时同时触发两条重试循环
+    pub reconnect_running: Arc<AtomicBool>,
+
+    // 脚本控制台执行线程的运行标志（见 script_console.rs），为 false 时执行线程
+    // 在下一条指令/下一次 REPEAT 迭代前退出
+    pub script_running: Arc<AtomicBool>,
+
+    // MQTT 桥接的运行标志（见 mqtt_bridge.rs），只在开启 `mqtt` feature 时存在
+    #[cfg(feature = "mqtt")]
+    pub mqtt_bridge_running: Arc<AtomicBool>,
 }
 
 impl Default for AppState {
@@ -77,9 +167,24 @@ impl Default for AppState {
             csv_loop_running: Arc::new(AtomicBool::new(false)),
             receive_thread_running: Arc::new(AtomicBool::new(false)),
             write_thread_running: Arc::new(AtomicBool::new(false)),
+            auto_drive_running: Arc::new(AtomicBool::new(false)),
 
             system_monitor_connected: Arc::new(Mutex::new(false)),
             system_monitor_thread_running: Arc::new(AtomicBool::new(false)),
+
+            cyclic_scheduler: Arc::new(Mutex::new(TimerWheel::new())),
+            io_port_id: Arc::new(Mutex::new(None)),
+            system_monitor_port_id: Arc::new(Mutex::new(None)),
+
+            port_monitor_running: Arc::new(AtomicBool::new(false)),
+            active_port: Arc::new(Mutex::new(None)),
+
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+            reconnect_running: Arc::new(AtomicBool::new(false)),
+            script_running: Arc::new(AtomicBool::new(false)),
+
+            #[cfg(feature = "mqtt")]
+            mqtt_bridge_running: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -105,7 +210,18 @@ pub fn run() {
             open_system_monitor_window,
             close_system_monitor_window,
             connect_system_monitor,
-            disconnect_system_monitor
+            disconnect_system_monitor,
+            start_infinite_drive,
+            stop_infinite_drive,
+            schedule_cyclic_message,
+            cancel_cyclic_message,
+            list_cyclic_messages,
+            connect_mqtt_bridge,
+            disconnect_mqtt_bridge,
+            get_tx_queue_stats,
+            execute_script,
+            stop_script,
+            send_isotp
         ])
         .setup(|app| {
             use log::info;
@@ -116,6 +232,10 @@ pub fn run() {
             let window = app.get_webview_window("main").unwrap();
             let app_handle = app.handle().clone();
 
+            // 启动串口热插拔监控
+            let monitor_state = app.state::<AppState>().inner().clone();
+            port_monitor::start_port_monitor(app_handle.clone(), monitor_state);
+
             // 监听窗口关闭事件
             window.on_window_event(move |event| {
                 if let tauri::WindowEvent::CloseRequested { .. } = event {
@@ -127,12 +247,26 @@ pub fn run() {
                         state.csv_loop_running.store(false, Ordering::SeqCst);
                         state.receive_thread_running.store(false, Ordering::SeqCst);
                         state.write_thread_running.store(false, Ordering::SeqCst);
+                        state.auto_drive_running.store(false, Ordering::SeqCst);
 
                         // Stop system monitor thread
                         state
                             .system_monitor_thread_running
                             .store(false, Ordering::SeqCst);
 
+                        // Stop port hotplug monitor thread
+                        state.port_monitor_running.store(false, Ordering::SeqCst);
+
+                        // 停止自动重连退避循环（心跳线程会在下一轮检查 is_connected 后自行退出）
+                        state.reconnect_running.store(false, Ordering::SeqCst);
+
+                        // 停止脚本控制台执行线程
+                        state.script_running.store(false, Ordering::SeqCst);
+
+                        // Stop MQTT bridge (no-op if the `mqtt` feature isn't compiled in)
+                        #[cfg(feature = "mqtt")]
+                        crate::mqtt_bridge::stop_mqtt_bridge(&state);
+
                         // 清理发送通道
                         if let Ok(mut tx_send) = state.tx_send.lock() {
                             *tx_send = None;
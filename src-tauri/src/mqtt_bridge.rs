@@ -0,0 +1,176 @@
+//! MQTT 桥接
+//!
+//! 把 I/O 线程解码出的 CAN 消息发布到 MQTT，同时订阅 `"{base_topic}/tx/#"`，
+//! 把远程下发的发送请求转换成发送包推到现有的 `tx_send` 通道，让远程系统能像
+//! 本地调用 `send_can_message` 一样驱动这路 CAN 总线。整个模块挂在 `mqtt` cargo
+//! feature 后面，不开启这个 feature 时不会被编译进二进制（依赖 `rumqttc`）。
+
+#![cfg(feature = "mqtt")]
+
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use rumqttc::{Client, Event, Incoming, MqttOptions, QoS};
+use serde::Deserialize;
+use tauri::Listener;
+
+use crate::can_protocol::{create_can_send_packet_fixed, create_can_send_packet_variable};
+use crate::{AppState, SendMessage};
+
+/// 远程下发的发送请求，经 `"{base_topic}/tx/..."` 投递，payload 为 JSON
+#[derive(Debug, Deserialize)]
+struct RemoteSendRequest {
+    id: String,
+    data: String,
+    #[serde(default = "default_frame_type")]
+    frame_type: String,
+    // "variable"，留空（默认）按定长协议处理，和 send_can_message 的约定一致
+    #[serde(default)]
+    protocol_length: String,
+}
+
+fn default_frame_type() -> String {
+    "standard".to_string()
+}
+
+fn qos_from_u8(qos: u8) -> QoS {
+    match qos {
+        1 => QoS::AtLeastOnce,
+        2 => QoS::ExactlyOnce,
+        _ => QoS::AtMostOnce,
+    }
+}
+
+/// 把 `"mqtt://host:port"`（或裸 `"host:port"`/`"host"`）解析成 rumqttc 需要的 host/port
+fn parse_broker_url(broker_url: &str) -> Result<(String, u16), String> {
+    let stripped = broker_url.strip_prefix("mqtt://").unwrap_or(broker_url);
+    let mut parts = stripped.splitn(2, ':');
+    let host = parts
+        .next()
+        .filter(|h| !h.is_empty())
+        .ok_or_else(|| "broker_url is missing a host".to_string())?
+        .to_string();
+    let port = match parts.next() {
+        Some(p) => p
+            .parse::<u16>()
+            .map_err(|_| "broker_url has an invalid port".to_string())?,
+        None => 1883,
+    };
+    Ok((host, port))
+}
+
+/// 启动 MQTT 桥接：连接到 `broker_url`，把每一条收到的 CAN 消息发布到
+/// `"{base_topic}/rx/{can_id}"`，并订阅 `"{base_topic}/tx/#"` 把远程下发的发送
+/// 请求转发进现有的 `tx_send` 通道
+pub fn start_mqtt_bridge(
+    app_handle: tauri::AppHandle,
+    state: AppState,
+    broker_url: String,
+    base_topic: String,
+    qos: u8,
+) -> Result<(), String> {
+    if state.mqtt_bridge_running.load(Ordering::SeqCst) {
+        return Err("MQTT bridge already running".to_string());
+    }
+
+    let (host, port) = parse_broker_url(&broker_url)?;
+    let mut mqtt_options = MqttOptions::new("usb-can-tauri", host, port);
+    mqtt_options.set_keep_alive(Duration::from_secs(5));
+
+    let (client, mut connection) = Client::new(mqtt_options, 64);
+
+    let tx_topic = format!("{}/tx/#", base_topic);
+    client
+        .subscribe(&tx_topic, qos_from_u8(qos))
+        .map_err(|e| format!("Failed to subscribe to {}: {}", tx_topic, e))?;
+
+    state.mqtt_bridge_running.store(true, Ordering::SeqCst);
+
+    // 发布侧：监听本地已有的 "can-message-received" 事件并原样转发到 MQTT，
+    // 不用往 I/O 线程的热路径里塞额外状态
+    let rx_topic_prefix = format!("{}/rx", base_topic);
+    let publish_state = state.clone();
+    let publish_client = client.clone();
+    let listener_handle = app_handle.clone();
+    let listener_id = listener_handle.listen("can-message-received", move |event| {
+        if !publish_state.mqtt_bridge_running.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(can_message) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+            return;
+        };
+        let can_id = can_message
+            .get("id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("UNKNOWN");
+        let topic = format!("{}/{}", rx_topic_prefix, can_id);
+        if let Err(e) = publish_client.publish(topic, qos_from_u8(qos), false, can_message.to_string()) {
+            warn!("MQTT bridge: publish failed: {}", e);
+        }
+    });
+
+    // 订阅侧：独立线程驱动 rumqttc 的事件循环，收到远程发送请求就转换成发送包
+    let sub_state = state.clone();
+    thread::spawn(move || {
+        info!("MQTT bridge: subscriber loop started on {}", tx_topic);
+
+        for notification in connection.iter() {
+            if !sub_state.mqtt_bridge_running.load(Ordering::SeqCst) {
+                break;
+            }
+            match notification {
+                Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                    handle_remote_publish(&sub_state, &publish.payload);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!("MQTT bridge: connection error: {}", e);
+                    break;
+                }
+            }
+        }
+
+        sub_state.mqtt_bridge_running.store(false, Ordering::SeqCst);
+        app_handle.unlisten(listener_id);
+        info!("MQTT bridge: subscriber loop stopped");
+    });
+
+    Ok(())
+}
+
+/// 停止 MQTT 桥接：清掉运行标志，订阅线程会在下一条消息或连接关闭时自然退出
+pub fn stop_mqtt_bridge(state: &AppState) {
+    state.mqtt_bridge_running.store(false, Ordering::SeqCst);
+}
+
+fn handle_remote_publish(state: &AppState, payload: &[u8]) {
+    let Ok(request) = serde_json::from_slice::<RemoteSendRequest>(payload) else {
+        warn!("MQTT bridge: ignoring malformed remote send request");
+        return;
+    };
+
+    let packet_result = if request.protocol_length == "variable" {
+        create_can_send_packet_variable(&request.id, &request.data, &request.frame_type)
+    } else {
+        create_can_send_packet_fixed(&request.id, &request.data, &request.frame_type)
+    };
+
+    let packet = match packet_result {
+        Ok(p) => p,
+        Err(e) => {
+            warn!("MQTT bridge: failed to build packet from remote request: {}", e);
+            return;
+        }
+    };
+
+    let tx_send = state.tx_send.lock().unwrap();
+    if let Some(ref queue) = *tx_send {
+        if let Err(e) = queue.try_enqueue(SendMessage { packet }) {
+            error!("MQTT bridge: failed to forward remote packet: {}", e);
+        }
+    } else {
+        warn!("MQTT bridge: received remote send request but no serial port connected");
+    }
+}
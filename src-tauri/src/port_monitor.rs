@@ -0,0 +1,64 @@
+//! 串口热插拔监控
+//!
+//! `serialport` 没有原生的热插拔回调，这里通过轮询 `available_ports()`，
+//! 和上一次快照做 `HashSet` 差集来模拟 arrival/removal 事件，参照 n-link
+//! 桌面端代码里 `device_arrived`/`device_left` 的事件命名习惯。
+
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use log::info;
+use tauri::Emitter;
+
+use crate::AppState;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 启动后台串口监控线程，轮询可用串口集合的变化并向主窗口推送事件：
+/// - `"port-added"` / `"port-removed"`：每个新增/移除的端口各发一次，payload 为端口名
+/// - `"active-port-lost"`：当前已连接的端口消失时额外发一次，方便 UI 立即响应
+///
+/// 通过 `state.port_monitor_running` 控制退出，断开连接或窗口关闭时清零即可让线程自然结束。
+pub fn start_port_monitor(app_handle: tauri::AppHandle, state: AppState) {
+    state.port_monitor_running.store(true, Ordering::SeqCst);
+
+    thread::spawn(move || {
+        let mut known_ports = current_port_set();
+        info!("Port monitor: started, {} port(s) known", known_ports.len());
+
+        while state.port_monitor_running.load(Ordering::SeqCst) {
+            thread::sleep(POLL_INTERVAL);
+
+            let current_ports = current_port_set();
+
+            for added in current_ports.difference(&known_ports) {
+                info!("Port monitor: port added - {}", added);
+                let _ = app_handle.emit("port-added", added);
+            }
+
+            for removed in known_ports.difference(&current_ports) {
+                info!("Port monitor: port removed - {}", removed);
+                let _ = app_handle.emit("port-removed", removed);
+
+                let active_port = state.active_port.lock().unwrap().clone();
+                if active_port.as_deref() == Some(removed.as_str()) {
+                    info!("Port monitor: active port lost - {}", removed);
+                    let _ = app_handle.emit("active-port-lost", removed);
+                }
+            }
+
+            known_ports = current_ports;
+        }
+
+        info!("Port monitor: stopped");
+    });
+}
+
+/// 拍一份当前可用串口名的快照
+fn current_port_set() -> HashSet<String> {
+    serialport::available_ports()
+        .map(|ports| ports.into_iter().map(|p| p.port_name).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,209 @@
+//! ARS408 风格的毫米波雷达目标列表解码
+//! 取代 `parse_distance_from_data` 只取一个距离标量的做法：按照 ARS408 的报文布局，
+//! 先收到一条对象状态报文（目标数量 + 测量计数器），随后是若干条逐目标的
+//! "general" 报文（目标 ID、纵向/横向距离、相对速度等）。按目标 ID 聚合一个
+//! 测量周期内的所有目标，在下一条状态报文到达时把上一周期的完整目标列表吐出去。
+
+use std::collections::HashMap;
+
+/// 对象状态报文 ID（Obj_0_Status），宣布本周期的目标数量
+pub const RADAR_STATUS_ID: u32 = 0x60A;
+/// 逐目标的 general 报文 ID（Obj_1_General）
+pub const RADAR_OBJECT_GENERAL_ID: u32 = 0x60B;
+
+/// 动态属性枚举（目标相对于自车的运动状态）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum DynamicProperty {
+    Moving,
+    Stationary,
+    Oncoming,
+    StationaryCandidate,
+    Unknown,
+    Crossing,
+    Stopped,
+}
+
+impl DynamicProperty {
+    fn from_raw(raw: u8) -> Self {
+        match raw & 0x07 {
+            0 => Self::Moving,
+            1 => Self::Stationary,
+            2 => Self::Oncoming,
+            3 => Self::StationaryCandidate,
+            5 => Self::Crossing,
+            6 => Self::Stopped,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// 测量状态枚举
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum MeasurementState {
+    Deleted,
+    New,
+    Measured,
+    Predicted,
+    DeletedForMerge,
+    NewFromMerge,
+}
+
+impl MeasurementState {
+    fn from_raw(raw: u8) -> Self {
+        match raw & 0x07 {
+            0 => Self::Deleted,
+            1 => Self::New,
+            2 => Self::Measured,
+            3 => Self::Predicted,
+            4 => Self::DeletedForMerge,
+            5 => Self::NewFromMerge,
+            _ => Self::Deleted,
+        }
+    }
+}
+
+/// 解码后的单个雷达目标
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RadarObject {
+    pub id: u8,
+    /// 纵向距离 (m)
+    pub longitudinal_distance: f32,
+    /// 横向距离 (m)
+    pub lateral_distance: f32,
+    /// 纵向相对速度 (m/s)
+    pub relative_velocity: f32,
+    /// 雷达散射截面 (dBm^2)
+    pub rcs: f32,
+    pub dynamic_property: DynamicProperty,
+    pub measurement_state: MeasurementState,
+}
+
+/// 解析一条 Obj_0_Status 报文，返回 (目标数量, 测量计数器)
+pub fn decode_object_status(data: &[u8]) -> Option<(u8, u8)> {
+    if data.len() < 2 {
+        return None;
+    }
+    Some((data[0], data[1]))
+}
+
+/// 解析一条 Obj_1_General 报文为单个目标
+///
+/// 字段布局（大端位域，跨字节打包）：
+/// - 字节0: Object_ID
+/// - 字节1 + 字节2高5位: Obj_DistLong，13位无符号，`raw * 0.2 - 500` (m)
+/// - 字节2低3位 + 字节3: Obj_DistLat，11位无符号，`raw * 0.2 - 204.6` (m)
+/// - 字节4 + 字节5高5位: Obj_RelVelLong，13位无符号，`raw * 0.25 - 128` (m/s)
+/// - 字节6: Obj_RCS，`raw * 0.5 - 64` (dBm^2)
+/// - 字节7低3位: Obj_DynProp
+/// - 字节7 bit3-5（右移3后取低3位）: Obj_MeasState
+pub fn decode_object_general(data: &[u8]) -> Option<RadarObject> {
+    if data.len() < 8 {
+        return None;
+    }
+
+    let id = data[0];
+
+    let dist_long_raw = ((data[1] as u16) << 5) | ((data[2] >> 3) as u16);
+    let longitudinal_distance = dist_long_raw as f32 * 0.2 - 500.0;
+
+    let dist_lat_raw = (((data[2] & 0x07) as u16) << 8) | data[3] as u16;
+    let lateral_distance = dist_lat_raw as f32 * 0.2 - 204.6;
+
+    let rel_vel_raw = ((data[4] as u16) << 5) | ((data[5] >> 3) as u16);
+    let relative_velocity = rel_vel_raw as f32 * 0.25 - 128.0;
+
+    let rcs = data[6] as f32 * 0.5 - 64.0;
+
+    let dynamic_property = DynamicProperty::from_raw(data[7] & 0x07);
+    let measurement_state = MeasurementState::from_raw((data[7] >> 3) & 0x07);
+
+    Some(RadarObject {
+        id,
+        longitudinal_distance,
+        lateral_distance,
+        relative_velocity,
+        rcs,
+        dynamic_property,
+        measurement_state,
+    })
+}
+
+/// 按测量周期聚合目标列表：状态报文到达时，把上一周期累积的目标列表交出去并重置
+pub struct RadarAggregator {
+    current_cycle: HashMap<u8, RadarObject>,
+    measurement_counter: Option<u8>,
+}
+
+impl RadarAggregator {
+    pub fn new() -> Self {
+        Self {
+            current_cycle: HashMap::new(),
+            measurement_counter: None,
+        }
+    }
+
+    /// 处理一条状态报文：返回上一周期累积的完整目标列表（如果有），并开始新周期
+    pub fn handle_status(&mut self, data: &[u8]) -> Option<(u8, Vec<RadarObject>)> {
+        let (_object_count, meas_counter) = decode_object_status(data)?;
+
+        let previous = if self.current_cycle.is_empty() {
+            None
+        } else {
+            let objects: Vec<RadarObject> = self.current_cycle.values().cloned().collect();
+            Some((self.measurement_counter.unwrap_or(meas_counter), objects))
+        };
+
+        self.current_cycle.clear();
+        self.measurement_counter = Some(meas_counter);
+        previous
+    }
+
+    /// 处理一条 general 报文，按目标 ID 累积到当前周期
+    pub fn handle_general(&mut self, data: &[u8]) {
+        if let Some(object) = decode_object_general(data) {
+            self.current_cycle.insert(object.id, object);
+        }
+    }
+}
+
+impl Default for RadarAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_object_general_fields() {
+        // raw13 dist_long = 2500 -> 2500*0.2-500 = 0.0
+        // byte1 = 2500 >> 5 = 78 (0x4E), byte2 high5 = (2500 & 0x1F) << 3 = 4 << 3 = 0x20
+        let data = [0x03, 0x4E, 0x20, 0x00, 0x00, 0x00, 0x80, 0x00];
+        let obj = decode_object_general(&data).unwrap();
+        assert_eq!(obj.id, 3);
+        assert!((obj.longitudinal_distance - 0.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn aggregates_one_cycle_then_flushes_on_next_status() {
+        let mut agg = RadarAggregator::new();
+
+        // First status just starts the cycle, nothing to flush yet
+        assert!(agg.handle_status(&[2, 1]).is_none());
+
+        agg.handle_general(&[1, 0x4E, 0x20, 0x00, 0x00, 0x00, 0x80, 0x00]);
+        agg.handle_general(&[2, 0x4E, 0x20, 0x00, 0x00, 0x00, 0x80, 0x00]);
+
+        let (meas_counter, objects) = agg.handle_status(&[2, 2]).unwrap();
+        assert_eq!(meas_counter, 1);
+        assert_eq!(objects.len(), 2);
+    }
+
+    #[test]
+    fn dynamic_property_and_measurement_state_decode() {
+        assert_eq!(DynamicProperty::from_raw(0), DynamicProperty::Moving);
+        assert_eq!(MeasurementState::from_raw(2), MeasurementState::Measured);
+    }
+}
@@ -0,0 +1,353 @@
+//! 单线程 I/O 多路复用 Reactor
+//! 取代"每个串口一个阻塞线程 + 5ms 轮询睡眠"的模型：一个事件循环线程通过 mio
+//! 注册所有串口的底层文件描述符，只在可读/可写时被唤醒，集中调度读写和关闭。
+//!
+//! 仅支持类 Unix 平台（通过 `mio::unix::SourceFd` 包装串口的裸 fd）。
+
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Token, Waker};
+use serialport::SerialPort;
+
+use crate::scheduler::TimerWheel;
+use crate::SendMessage;
+
+/// 每隔多久在没有 I/O 事件的情况下也唤醒一次事件循环，用于驱动挂载的周期发送调度器
+const SCHEDULER_TICK_INTERVAL: Duration = Duration::from_millis(1);
+
+/// 某一路串口收到数据后的处理回调，运行在 Reactor 线程内，应尽快返回
+pub type FrameHandler = Box<dyn FnMut(&[u8]) + Send>;
+
+/// 读写出现硬错误（非超时/WouldBlock）导致这路串口被摘除时触发一次，
+/// 用于驱动上层的自动重连子系统；同样运行在 Reactor 线程内，应尽快返回
+pub type DisconnectHandler = Box<dyn Fn() + Send>;
+
+/// 把一个待发送的原始包编码成这路串口实际要写到线上的字节（见 `framing.rs` 的
+/// `Framing::encode_for_send`），运行在 Reactor 线程内，应尽快返回
+pub type WriteEncoder = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+
+/// 挂起的注册/注销请求，在事件循环里统一处理，避免从外部线程直接操作 `Poll`
+enum RegistryCommand {
+    Add {
+        port_id: u64,
+        port: Box<dyn SerialPort>,
+        handler: FrameHandler,
+        write_rx: Receiver<SendMessage>,
+        scheduler: Option<Arc<Mutex<TimerWheel>>>,
+        on_disconnect: Option<DisconnectHandler>,
+        tx_depth: Option<Arc<AtomicU64>>,
+        write_encode: Option<WriteEncoder>,
+    },
+    Remove {
+        port_id: u64,
+    },
+}
+
+struct RegisteredPort {
+    port: Box<dyn SerialPort>,
+    handler: FrameHandler,
+    write_rx: Receiver<SendMessage>,
+    /// 挂在这路串口上的周期发送调度器（如果有），随事件循环的节拍一起推进
+    scheduler: Option<Arc<Mutex<TimerWheel>>>,
+    last_tick: Instant,
+    /// 读写出现硬错误时触发一次，交给上层（通常是自动重连子系统）处理
+    on_disconnect: Option<DisconnectHandler>,
+    /// `write_rx` 对应的有界发送队列深度计数器（见 `tx_queue.rs`），每取走一个包就递减一次；
+    /// 系统监控那路只读串口没有挂真正的 `TxQueue`，这里是 `None`
+    tx_depth: Option<Arc<AtomicU64>>,
+    /// 发送前按这路串口选定的成帧方式重新编码（见 `framing.rs`），`None` 时原样写出
+    write_encode: Option<WriteEncoder>,
+}
+
+/// 多串口事件循环：`add_port`/`remove_port` 可在任意线程调用，实际的注册/注销
+/// 在 Reactor 自己的线程里通过命令队列串行处理。
+pub struct Reactor {
+    commands: Sender<RegistryCommand>,
+    waker: Waker,
+    next_port_id: AtomicU64,
+}
+
+impl Reactor {
+    /// 启动事件循环线程，返回可供多个线程共享的 Reactor 句柄
+    pub fn start() -> std::io::Result<Self> {
+        let poll = Poll::new()?;
+        let waker = Waker::new(poll.registry(), WAKE_TOKEN)?;
+        let (cmd_tx, cmd_rx) = mpsc::channel();
+
+        thread::spawn(move || run_event_loop(poll, cmd_rx));
+
+        Ok(Self {
+            commands: cmd_tx,
+            waker,
+            next_port_id: AtomicU64::new(1),
+        })
+    }
+
+    /// 注册一路串口：`handler` 在每次读到数据时被调用，`write_rx` 是该串口专属的
+    /// 写请求通道，Reactor 会在 fd 可写时尽量排空它
+    pub fn add_port(
+        &self,
+        port: Box<dyn SerialPort>,
+        handler: FrameHandler,
+        write_rx: Receiver<SendMessage>,
+    ) -> u64 {
+        self.add_port_with_scheduler(port, handler, write_rx, None)
+    }
+
+    /// 同 [`Reactor::add_port`]，并额外挂载一个周期发送调度器，事件循环会按真实
+    /// 流逝的时间推进它并把到期的包直接写入这路串口
+    pub fn add_port_with_scheduler(
+        &self,
+        port: Box<dyn SerialPort>,
+        handler: FrameHandler,
+        write_rx: Receiver<SendMessage>,
+        scheduler: Option<Arc<Mutex<TimerWheel>>>,
+    ) -> u64 {
+        self.add_port_with_options(port, handler, write_rx, scheduler, None, None, None)
+    }
+
+    /// 同 [`Reactor::add_port_with_scheduler`]，并额外挂载一个 `on_disconnect` 回调
+    /// （在这路串口读写出现硬错误而被摘除时触发一次，用于驱动自动重连）、一个
+    /// `tx_depth` 深度计数器（挂了有界发送队列的串口才有，见 `tx_queue.rs`），以及一个
+    /// `write_encode` 发送前编码回调（见 `framing.rs` 的 `Framing::encode_for_send`）
+    pub fn add_port_with_options(
+        &self,
+        port: Box<dyn SerialPort>,
+        handler: FrameHandler,
+        write_rx: Receiver<SendMessage>,
+        scheduler: Option<Arc<Mutex<TimerWheel>>>,
+        on_disconnect: Option<DisconnectHandler>,
+        tx_depth: Option<Arc<AtomicU64>>,
+        write_encode: Option<WriteEncoder>,
+    ) -> u64 {
+        let port_id = self.next_port_id.fetch_add(1, Ordering::SeqCst);
+        let _ = self.commands.send(RegistryCommand::Add {
+            port_id,
+            port,
+            handler,
+            write_rx,
+            scheduler,
+            on_disconnect,
+            tx_depth,
+            write_encode,
+        });
+        if let Err(e) = self.waker.wake() {
+            warn!("Reactor: failed to wake event loop after add_port: {}", e);
+        }
+        port_id
+    }
+
+    /// 注销一路串口，停止其读写
+    pub fn remove_port(&self, port_id: u64) {
+        let _ = self.commands.send(RegistryCommand::Remove { port_id });
+        if let Err(e) = self.waker.wake() {
+            warn!("Reactor: failed to wake event loop after remove_port: {}", e);
+        }
+    }
+
+    /// 进程内唯一的 Reactor 实例，所有串口都注册到同一个事件循环线程上
+    pub fn global() -> &'static Reactor {
+        static REACTOR: OnceLock<Reactor> = OnceLock::new();
+        REACTOR.get_or_init(|| Reactor::start().expect("failed to start I/O reactor"))
+    }
+}
+
+/// Waker 固定使用 token 0，串口 token 从 1 开始按 `port_id` 偏移
+const WAKE_TOKEN: Token = Token(0);
+
+fn run_event_loop(mut poll: Poll, cmd_rx: Receiver<RegistryCommand>) {
+    info!("🚀 [Reactor] Event loop started");
+
+    let mut ports: HashMap<u64, RegisteredPort> = HashMap::new();
+    let mut events = Events::with_capacity(64);
+    let mut read_buf = vec![0u8; 4096];
+
+    loop {
+        // 有界超时：即使没有任何 fd 就绪，也能定期醒来推进挂载的周期发送调度器
+        if let Err(e) = poll.poll(&mut events, Some(SCHEDULER_TICK_INTERVAL)) {
+            if e.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            error!("Reactor: poll failed: {}", e);
+            break;
+        }
+
+        let mut failed_ports: Vec<u64> = Vec::new();
+
+        for event in events.iter() {
+            if event.token() == WAKE_TOKEN {
+                // 处理挂起的注册/注销命令
+                while let Ok(cmd) = cmd_rx.try_recv() {
+                    apply_command(cmd, &mut ports, &poll);
+                }
+                continue;
+            }
+
+            let port_id = token_to_port_id(event.token());
+            let Some(registered) = ports.get_mut(&port_id) else {
+                continue;
+            };
+
+            if event.is_readable() {
+                match registered.port.read(&mut read_buf) {
+                    Ok(n) if n > 0 => (registered.handler)(&read_buf[..n]),
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => {
+                        warn!("Reactor: read error on port {}: {}", port_id, e);
+                        failed_ports.push(port_id);
+                    }
+                }
+            }
+
+            if event.is_writable() && !failed_ports.contains(&port_id) {
+                if drain_write_channel(registered, port_id).is_err() {
+                    failed_ports.push(port_id);
+                }
+            }
+        }
+
+        // 无论本轮有没有 I/O 事件，都顺带驱动每路串口挂载的周期调度器并排空写请求
+        for (&port_id, registered) in ports.iter_mut() {
+            if failed_ports.contains(&port_id) {
+                continue;
+            }
+            if drain_write_channel(registered, port_id).is_err() {
+                failed_ports.push(port_id);
+                continue;
+            }
+            tick_scheduler(registered, port_id);
+        }
+
+        // 读写硬错误：摘除这路串口并通知上层（通常是自动重连子系统）
+        for port_id in failed_ports {
+            if let Some(registered) = ports.remove(&port_id) {
+                let fd = registered.port.as_raw_fd();
+                let mut source = SourceFd(&fd);
+                let _ = poll.registry().deregister(&mut source);
+                warn!("Reactor: port {} removed after I/O error", port_id);
+                if let Some(on_disconnect) = &registered.on_disconnect {
+                    on_disconnect();
+                }
+            }
+        }
+    }
+
+    info!("Reactor: event loop exited");
+}
+
+/// 排空这路串口的写请求通道；返回 `Err` 表示写入时遇到了硬错误（调用方应摘除这路串口）
+fn drain_write_channel(registered: &mut RegisteredPort, port_id: u64) -> Result<(), ()> {
+    while let Ok(msg) = registered.write_rx.try_recv() {
+        if let Some(depth) = &registered.tx_depth {
+            depth.fetch_sub(1, Ordering::SeqCst);
+        }
+        let on_wire = match &registered.write_encode {
+            Some(encode) => encode(&msg.packet),
+            None => msg.packet,
+        };
+        if let Err(e) = registered.port.write_all(&on_wire) {
+            warn!("Reactor: write error on port {}: {}", port_id, e);
+            return Err(());
+        }
+    }
+    let _ = registered.port.flush();
+    Ok(())
+}
+
+fn tick_scheduler(registered: &mut RegisteredPort, port_id: u64) {
+    let Some(scheduler) = &registered.scheduler else {
+        return;
+    };
+
+    let elapsed_ms = registered.last_tick.elapsed().as_millis() as u64;
+    if elapsed_ms == 0 {
+        return;
+    }
+    registered.last_tick = Instant::now();
+
+    let due_packets = {
+        let mut wheel = scheduler.lock().unwrap();
+        let mut due = Vec::new();
+        for _ in 0..elapsed_ms {
+            due.extend(wheel.tick());
+        }
+        due
+    };
+
+    for packet in due_packets {
+        let on_wire = match &registered.write_encode {
+            Some(encode) => encode(&packet),
+            None => packet,
+        };
+        if let Err(e) = registered.port.write_all(&on_wire) {
+            warn!("Reactor: cyclic send failed on port {}: {}", port_id, e);
+        }
+    }
+    let _ = registered.port.flush();
+}
+
+fn apply_command(cmd: RegistryCommand, ports: &mut HashMap<u64, RegisteredPort>, poll: &Poll) {
+    match cmd {
+        RegistryCommand::Add {
+            port_id,
+            mut port,
+            handler,
+            write_rx,
+            scheduler,
+            on_disconnect,
+            tx_depth,
+            write_encode,
+        } => {
+            let fd = port.as_raw_fd();
+            let mut source = SourceFd(&fd);
+            if let Err(e) = poll
+                .registry()
+                .register(&mut source, port_to_token(port_id), Interest::READABLE | Interest::WRITABLE)
+            {
+                error!("Reactor: failed to register port {}: {}", port_id, e);
+                return;
+            }
+
+            ports.insert(
+                port_id,
+                RegisteredPort {
+                    port,
+                    handler,
+                    write_rx,
+                    scheduler,
+                    last_tick: Instant::now(),
+                    on_disconnect,
+                    tx_depth,
+                    write_encode,
+                },
+            );
+            info!("Reactor: registered port {}", port_id);
+        }
+        RegistryCommand::Remove { port_id } => {
+            if let Some(mut registered) = ports.remove(&port_id) {
+                let fd = registered.port.as_raw_fd();
+                let mut source = SourceFd(&fd);
+                let _ = poll.registry().deregister(&mut source);
+                info!("Reactor: removed port {}", port_id);
+            }
+        }
+    }
+}
+
+fn port_to_token(port_id: u64) -> Token {
+    Token(port_id as usize)
+}
+
+fn token_to_port_id(token: Token) -> u64 {
+    token.0 as u64
+}
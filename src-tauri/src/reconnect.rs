@@ -0,0 +1,145 @@
+//! 自动重连 + 链路健康心跳
+//!
+//! Reactor 只负责发现硬件层面的读写错误（见 `reactor.rs` 的 `on_disconnect`），
+//! 但串口本身也可能"假死"：fd 仍然正常，对端却早已停止发送。心跳线程定期检查
+//! `AppState::last_activity`，超过阈值没收到任何帧就当作链路失活来处理。
+//! 两条触发路径（硬件错误 / 心跳超时）最终都走同一个指数退避重连循环。
+
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use tauri::Emitter;
+
+use crate::io_thread::start_io_thread;
+use crate::tx_queue::TxQueue;
+use crate::{AppState, SerialConfig};
+
+/// 心跳检测间隔
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+/// 超过这么久没收到任何帧就认为链路失活
+const STALE_THRESHOLD: Duration = Duration::from_secs(5);
+/// 重连退避的起始等待时间，每次失败翻倍，上限由 `config.max_backoff_ms` 给出
+const INITIAL_BACKOFF_MS: u64 = 100;
+
+/// 启动链路健康心跳线程：轮询 `state.is_connected` 判断是否该退出，
+/// 超过 `STALE_THRESHOLD` 未收到任何帧就发出 `"link-stale"` 并触发一轮重连
+pub fn start_heartbeat(app_handle: tauri::AppHandle, state: AppState, config: SerialConfig) {
+    thread::spawn(move || {
+        info!(
+            "Heartbeat: started, stale threshold {:?}",
+            STALE_THRESHOLD
+        );
+
+        while *state.is_connected.lock().unwrap() {
+            thread::sleep(HEARTBEAT_INTERVAL);
+
+            if !*state.is_connected.lock().unwrap() {
+                break;
+            }
+
+            let idle = state.last_activity.lock().unwrap().elapsed();
+            if idle > STALE_THRESHOLD {
+                warn!("Heartbeat: link stale, no frame received for {:?}", idle);
+                let _ = app_handle.emit("link-stale", idle.as_millis() as u64);
+                trigger_reconnect(app_handle.clone(), state.clone(), config.clone());
+                break;
+            }
+        }
+
+        info!("Heartbeat: stopped");
+    });
+}
+
+/// 以指数退避 + 抖动反复尝试重新打开串口，并重走一遍 `start_io_thread` 的注册流程。
+/// `reactor.rs` 的 `on_disconnect` 回调和心跳线程都可能调用这个函数，用
+/// `reconnect_running` 防止两条触发路径同时跑出两条重试循环。
+pub fn trigger_reconnect(app_handle: tauri::AppHandle, state: AppState, config: SerialConfig) {
+    if !config.reconnect {
+        return;
+    }
+
+    if state
+        .reconnect_running
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return; // 已经有一轮重试在跑了
+    }
+
+    thread::spawn(move || {
+        let mut backoff_ms = INITIAL_BACKOFF_MS;
+
+        let result = loop {
+            if !*state.is_connected.lock().unwrap() {
+                // disconnect_serial 已经把连接状态清掉了，放弃重试
+                break None;
+            }
+
+            thread::sleep(Duration::from_millis(backoff_ms + jitter_ms(backoff_ms)));
+
+            info!("Reconnect: attempting to reopen {}", config.port);
+            let _ = app_handle.emit("reconnecting", &config.port);
+
+            match serialport::new(&config.port, config.baud_rate)
+                .timeout(Duration::from_millis(1000))
+                .open()
+            {
+                Ok(port) => break Some(port),
+                Err(e) => {
+                    warn!("Reconnect: failed to reopen {}: {}", config.port, e);
+                    let _ = app_handle.emit("reconnect-failed", format!("{}", e));
+                    backoff_ms = (backoff_ms * 2).min(config.max_backoff_ms);
+                }
+            }
+        };
+
+        if let Some(port) = result {
+            let (tx_queue, rx_send, tx_depth) = TxQueue::bounded(config.tx_queue_size);
+            {
+                let mut tx_send_guard = state.tx_send.lock().unwrap();
+                *tx_send_guard = Some(tx_queue.clone());
+            }
+            *state.last_activity.lock().unwrap() = Instant::now();
+
+            let framing = crate::framing::from_name(&config.framing);
+            let port_id = start_io_thread(
+                port,
+                state.clone(),
+                rx_send,
+                app_handle.clone(),
+                framing,
+                config.clone(),
+                tx_depth,
+            );
+            {
+                let mut io_port_id = state.io_port_id.lock().unwrap();
+                *io_port_id = Some(port_id);
+            }
+            tx_queue.start_saturation_watcher(app_handle.clone(), state.write_thread_running.clone());
+
+            let packet = crate::can_protocol::create_can_config_packet(&config);
+            if let Err(e) = tx_queue.try_enqueue(crate::SendMessage { packet }) {
+                warn!("Reconnect: failed to resend CAN config packet: {}", e);
+            }
+
+            info!("Reconnect: {} reopened successfully", config.port);
+            let _ = app_handle.emit("reconnected", &config.port);
+
+            start_heartbeat(app_handle, state.clone(), config);
+        }
+
+        state.reconnect_running.store(false, Ordering::SeqCst);
+    });
+}
+
+/// 退避等待时间的抖动量，取当前纳秒时间戳的低位再模上退避基数的四分之一，
+/// 避免多路串口的重连请求撞在同一个时间点上（没有 `rand` 依赖，借用时间戳凑数）
+fn jitter_ms(backoff_ms: u64) -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    nanos % (backoff_ms / 4 + 1)
+}
@@ -0,0 +1,441 @@
+//! CAN 抓包的录制/回放日志格式
+//!
+//! 目前抓到的流量只会通过 `can-message-received` 事件推给前端，没有任何落盘、也没有
+//! 办法把一段历史流量重新喂回解析器做回归测试。这里借鉴 protobuf 那类紧凑二进制格式
+//! 的思路：每条记录都是自描述的 `1 字节方向标签 + varint 长度前缀 + payload`，payload
+//! 里再塞一个微秒级时间戳和 [`CanFrame`] 本身。自描述的好处是：
+//! - 写入端崩溃/掉电导致文件尾部只写了一半时，读取端能按"长度前缀说的字节数不够"
+//!   识别出这是截断的尾巴，安静地停止迭代，而不是把后面本不存在的数据当成下一条
+//!   记录硬解析出一堆垃圾；
+//! - 不需要额外的文件头/索引就能流式读取，边读边 replay 进
+//!   [`crate::can_frame::CanFrame::from_rx_message`]/`signal_db::decode` 这些下游接口。
+//!
+//! 记录里的方向标签只做 RX/TX 这两种区分，更细的 metadata（来源子系统、序号等）
+//! 不在这个格式的范围内。
+//!
+//! 模块没有叫 `log`：这个 crate 到处用 `log::info!`/`log::warn!`（见 `lib.rs` 里的
+//! `env_logger::init()`），和同名的本地模块放在一起会在 2018+ 版本的统一路径解析下
+//! 产生"`log` 到底指 crate 还是模块"的歧义，所以这里叫 `replay_log`。
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::can_frame::CanFrame;
+
+/// 这条记录是收到的帧还是发出的帧
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+impl Direction {
+    fn tag(self) -> u8 {
+        match self {
+            Direction::Rx => 0x00,
+            Direction::Tx => 0x01,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0x00 => Some(Direction::Rx),
+            0x01 => Some(Direction::Tx),
+            _ => None,
+        }
+    }
+}
+
+/// 从日志里读出来的一条完整记录
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedFrame {
+    pub timestamp_us: u64,
+    pub direction: Direction,
+    pub frame: CanFrame,
+}
+
+/// 读取/解析日志时能失败的具体原因
+#[derive(Debug)]
+pub enum LogError {
+    /// 底层文件 I/O 失败（不是"读到文件尾"这种正常情况）
+    Io(io::Error),
+    /// 方向标签既不是 RX(0x00) 也不是 TX(0x01)
+    UnknownDirectionTag(u8),
+    /// payload 内部字段自相矛盾（比如声明的数据长度超出了实际 payload 大小），
+    /// 和"文件尾被截断"是两回事——这种记录的长度前缀是完整的，只是内容本身有问题
+    CorruptRecord(String),
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::Io(e) => write!(f, "log I/O error: {}", e),
+            LogError::UnknownDirectionTag(tag) => {
+                write!(f, "unknown log record direction tag: 0x{:02X}", tag)
+            }
+            LogError::CorruptRecord(msg) => write!(f, "corrupt log record: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LogError {}
+
+impl From<io::Error> for LogError {
+    fn from(e: io::Error) -> Self {
+        LogError::Io(e)
+    }
+}
+
+/// 把一个无符号整数编码成 LEB128 风格的 varint（每字节低 7 位是数据，最高位是
+/// "后面还有字节"的续传标志）
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value != 0 {
+            buf.push(byte | 0x80);
+        } else {
+            buf.push(byte);
+            break;
+        }
+    }
+}
+
+/// [`write_varint`] 的反函数；`Ok(None)` 表示在凑齐一个完整 varint 之前就遇到了文件尾
+/// ——调用方应当把这当成"写入端只写了一半就停了"，而不是真正的错误
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value: u64 = 0;
+    let mut shift = 0u32;
+    loop {
+        let mut byte = [0u8; 1];
+        if reader.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "varint too long"));
+        }
+    }
+}
+
+/// payload 布局（不含外层的方向标签/长度前缀）：
+/// - 字节0-7：微秒级时间戳，u64 小端序
+/// - 字节8-11：CAN ID，u32 小端序
+/// - 字节12：标志位（bit0=扩展帧，bit1=FD 帧，bit2=远程帧）
+/// - 字节13：数据长度
+/// - 字节14+：数据
+fn encode_payload(timestamp_us: u64, frame: &CanFrame) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(14 + frame.data.len());
+    payload.extend_from_slice(&timestamp_us.to_le_bytes());
+    payload.extend_from_slice(&frame.id.to_le_bytes());
+
+    let mut flags = 0u8;
+    if frame.extended {
+        flags |= 0x01;
+    }
+    if frame.fd {
+        flags |= 0x02;
+    }
+    if frame.remote {
+        flags |= 0x04;
+    }
+    payload.push(flags);
+    payload.push(frame.data.len() as u8);
+    payload.extend_from_slice(&frame.data);
+
+    payload
+}
+
+fn decode_payload(payload: &[u8]) -> Result<(u64, CanFrame), LogError> {
+    if payload.len() < 14 {
+        return Err(LogError::CorruptRecord(format!(
+            "record payload too short: got {} bytes, need at least 14",
+            payload.len()
+        )));
+    }
+
+    let timestamp_us = u64::from_le_bytes(payload[0..8].try_into().unwrap());
+    let can_id = u32::from_le_bytes(payload[8..12].try_into().unwrap());
+    let flags = payload[12];
+    let data_len = payload[13] as usize;
+    let data = payload
+        .get(14..14 + data_len)
+        .ok_or_else(|| {
+            LogError::CorruptRecord(format!(
+                "declared data_len {} exceeds payload size {}",
+                data_len,
+                payload.len()
+            ))
+        })?
+        .to_vec();
+
+    let frame = CanFrame {
+        id: can_id,
+        extended: flags & 0x01 != 0,
+        fd: flags & 0x02 != 0,
+        remote: flags & 0x04 != 0,
+        data,
+        // 这个日志格式目前不序列化 FrameHeader（见 encode_payload），回放出来的帧
+        // 一律当作没有挂 header
+        header: None,
+    };
+
+    Ok((timestamp_us, frame))
+}
+
+/// 把一条记录（方向 + 时间戳 + 帧）写成 `标签 + varint 长度 + payload` 追加到 `writer`
+pub fn write_record<W: Write>(
+    writer: &mut W,
+    direction: Direction,
+    timestamp_us: u64,
+    frame: &CanFrame,
+) -> io::Result<()> {
+    let payload = encode_payload(timestamp_us, frame);
+
+    let mut header = vec![direction.tag()];
+    write_varint(&mut header, payload.len() as u64);
+
+    writer.write_all(&header)?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+/// 顺序写入录制日志的句柄，内部带一层 `BufWriter` 减少系统调用次数
+pub struct LogWriter {
+    writer: BufWriter<File>,
+}
+
+impl LogWriter {
+    /// 新建（或truncate 已有的）日志文件
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    /// 在已有日志文件末尾继续追加，文件不存在时新建
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn write_frame(
+        &mut self,
+        direction: Direction,
+        timestamp_us: u64,
+        frame: &CanFrame,
+    ) -> io::Result<()> {
+        write_record(&mut self.writer, direction, timestamp_us, frame)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// 从任意 [`Read`] 惰性读出一条条 [`LoggedFrame`] 的流式读取器；一旦遇到文件尾（包括
+/// 只写了一半的截断记录）或者不可恢复的错误，后续 `next()` 一律返回 `None`
+pub struct LogReader<R> {
+    reader: R,
+    done: bool,
+}
+
+impl LogReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+            done: false,
+        })
+    }
+}
+
+impl<R: Read> LogReader<R> {
+    /// 直接包一个已有的 reader（测试、内存回放等场景用，不需要真的落盘成文件）
+    pub fn from_reader(reader: R) -> Self {
+        Self { reader, done: false }
+    }
+}
+
+impl<R: Read> Iterator for LogReader<R> {
+    type Item = Result<LoggedFrame, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut tag_buf = [0u8; 1];
+        match self.reader.read(&mut tag_buf) {
+            Ok(0) => {
+                // 干净的文件尾：上一条记录完整写完，后面再没有字节了
+                self.done = true;
+                return None;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.done = true;
+                return Some(Err(LogError::Io(e)));
+            }
+        }
+
+        let direction = match Direction::from_tag(tag_buf[0]) {
+            Some(d) => d,
+            None => {
+                self.done = true;
+                return Some(Err(LogError::UnknownDirectionTag(tag_buf[0])));
+            }
+        };
+
+        let len = match read_varint(&mut self.reader) {
+            Ok(Some(len)) => len as usize,
+            Ok(None) => {
+                // 标签字节写出去了，但长度前缀没写完——截断的尾巴，安静地结束
+                self.done = true;
+                return None;
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(LogError::Io(e)));
+            }
+        };
+
+        let mut payload = vec![0u8; len];
+        if let Err(e) = self.reader.read_exact(&mut payload) {
+            self.done = true;
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                // payload 也被截断了，同样当成正常的文件尾处理
+                return None;
+            }
+            return Some(Err(LogError::Io(e)));
+        }
+
+        match decode_payload(&payload) {
+            Ok((timestamp_us, frame)) => Some(Ok(LoggedFrame {
+                timestamp_us,
+                direction,
+                frame,
+            })),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample_frame() -> CanFrame {
+        CanFrame {
+            id: 0x18C4D2D0,
+            extended: true,
+            fd: false,
+            remote: false,
+            data: vec![0x01, 0x02, 0x03],
+            header: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_a_single_record() {
+        let mut buf = Vec::new();
+        write_record(&mut buf, Direction::Rx, 123_456, &sample_frame()).unwrap();
+
+        let mut reader = LogReader::from_reader(Cursor::new(buf));
+        let logged = reader.next().unwrap().unwrap();
+        assert_eq!(logged.timestamp_us, 123_456);
+        assert_eq!(logged.direction, Direction::Rx);
+        assert_eq!(logged.frame, sample_frame());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn round_trips_multiple_records_in_order() {
+        let mut buf = Vec::new();
+        write_record(&mut buf, Direction::Rx, 1, &sample_frame()).unwrap();
+        write_record(
+            &mut buf,
+            Direction::Tx,
+            2,
+            &CanFrame { id: 0x123, extended: false, fd: false, remote: true, data: vec![], header: None },
+        )
+        .unwrap();
+
+        let reader = LogReader::from_reader(Cursor::new(buf));
+        let frames: Vec<_> = reader.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].direction, Direction::Rx);
+        assert_eq!(frames[1].direction, Direction::Tx);
+        assert!(frames[1].frame.remote);
+    }
+
+    #[test]
+    fn truncated_tail_is_tolerated_not_reported_as_an_error() {
+        let mut buf = Vec::new();
+        write_record(&mut buf, Direction::Rx, 1, &sample_frame()).unwrap();
+        write_record(&mut buf, Direction::Tx, 2, &sample_frame()).unwrap();
+
+        // 模拟掉电：只写了第二条记录的前几个字节就断了
+        buf.truncate(buf.len() - 3);
+
+        let reader = LogReader::from_reader(Cursor::new(buf));
+        let results: Vec<_> = reader.collect();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_ok());
+    }
+
+    #[test]
+    fn empty_input_yields_no_records() {
+        let reader = LogReader::from_reader(Cursor::new(Vec::new()));
+        let results: Vec<_> = reader.collect();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn unknown_direction_tag_is_reported_as_an_error() {
+        let buf = vec![0xFFu8, 0x00];
+        let mut reader = LogReader::from_reader(Cursor::new(buf));
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(err, LogError::UnknownDirectionTag(0xFF)));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn declared_data_len_exceeding_payload_is_a_corrupt_record_not_a_truncation() {
+        // payload 内部字段自相矛盾：data_len 说有 5 个字节，但 payload 总共只留了 14
+        // 字节（正好是头部大小，没有数据），长度前缀本身是完整、正确的
+        let mut payload = vec![0u8; 14];
+        payload[13] = 5;
+
+        let mut buf = vec![Direction::Rx.tag()];
+        write_varint(&mut buf, payload.len() as u64);
+        buf.extend_from_slice(&payload);
+
+        let mut reader = LogReader::from_reader(Cursor::new(buf));
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(err, LogError::CorruptRecord(_)));
+    }
+
+    #[test]
+    fn varint_round_trips_values_spanning_multiple_bytes() {
+        for value in [0u64, 1, 127, 128, 300, 16384, u32::MAX as u64] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value);
+            let mut cursor = Cursor::new(buf);
+            assert_eq!(read_varint(&mut cursor).unwrap(), Some(value));
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! 固定容量环形缓冲区
+//! 用于替代 I/O 线程里 `Vec::drain` 的重组方式，避免每帧都做 O(n) 的内存搬移。
+
+/// 单生产者单消费者的字节环形缓冲区
+///
+/// `head` 指向下一个可读字节，`tail` 指向下一个可写位置，容量固定，
+/// 索引通过对 `capacity` 取模实现回绕。缓冲区满时 `enqueue` 会丢弃多余的字节，
+/// 调用方可以在入队前通过 [`RingBuffer::free_space`] 自行判断。
+pub struct RingBuffer {
+    storage: Vec<u8>,
+    capacity: usize,
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    /// 创建一个容量为 `capacity` 字节的环形缓冲区
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            storage: vec![0u8; capacity],
+            capacity,
+            head: 0,
+            tail: 0,
+            len: 0,
+        }
+    }
+
+    /// 当前已缓冲的字节数
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 还能写入多少字节而不覆盖未读数据
+    pub fn free_space(&self) -> usize {
+        self.capacity - self.len
+    }
+
+    /// 批量写入 `data`，返回实际写入的字节数（缓冲区满时会截断）
+    pub fn enqueue(&mut self, data: &[u8]) -> usize {
+        let n = data.len().min(self.free_space());
+        for &byte in &data[..n] {
+            self.storage[self.tail] = byte;
+            self.tail = (self.tail + 1) % self.capacity;
+        }
+        self.len += n;
+        n
+    }
+
+    /// 向前移动读游标 `n` 字节（丢弃这部分数据），`n` 会被截断到当前长度
+    pub fn advance(&mut self, n: usize) {
+        let n = n.min(self.len);
+        self.head = (self.head + n) % self.capacity;
+        self.len -= n;
+    }
+
+    /// 查看从读游标开始、长度为 `n` 的一段连续窗口
+    ///
+    /// 若这段数据在底层数组中跨越了回绕点，会被拷贝进 `scratch` 并返回
+    /// 指向 `scratch` 的切片；否则直接返回底层存储的切片，不产生拷贝。
+    /// `n` 超过当前已缓冲长度时返回 `None`。
+    pub fn peek<'a>(&'a self, n: usize, scratch: &'a mut [u8]) -> Option<&'a [u8]> {
+        if n > self.len {
+            return None;
+        }
+
+        let first_run = (self.capacity - self.head).min(n);
+        if first_run == n {
+            // 连续，无需跨越回绕点
+            return Some(&self.storage[self.head..self.head + n]);
+        }
+
+        // 跨越回绕点：先拷贝尾部，再拷贝从数组起始处续上的部分
+        assert!(scratch.len() >= n, "scratch buffer too small for peek");
+        scratch[..first_run].copy_from_slice(&self.storage[self.head..self.capacity]);
+        scratch[first_run..n].copy_from_slice(&self.storage[..n - first_run]);
+        Some(&scratch[..n])
+    }
+
+    /// 从读游标开始查找字节对 `needle`（例如消息头 `[0xAA, 0x55]`），
+    /// 返回相对读游标的偏移量
+    pub fn find(&self, needle: &[u8; 2]) -> Option<usize> {
+        self.find_from(0, needle)
+    }
+
+    /// 从读游标偏移 `start` 处开始查找字节对 `needle`，返回相对读游标的偏移量
+    pub fn find_from(&self, start: usize, needle: &[u8; 2]) -> Option<usize> {
+        if start + 2 > self.len {
+            return None;
+        }
+
+        let mut scratch = [0u8; 2];
+        for offset in start..=self.len - 2 {
+            // 每次只需要看 2 个字节，借助 peek 处理可能的回绕
+            if let Some(window) = self.peek_at(offset, 2, &mut scratch) {
+                if window == needle {
+                    return Some(offset);
+                }
+            }
+        }
+        None
+    }
+
+    /// 查看从读游标偏移 `offset` 处、长度为 `n` 的一段窗口
+    fn peek_at<'a>(&'a self, offset: usize, n: usize, scratch: &'a mut [u8]) -> Option<&'a [u8]> {
+        if offset + n > self.len {
+            return None;
+        }
+
+        let start = (self.head + offset) % self.capacity;
+        let first_run = (self.capacity - start).min(n);
+        if first_run == n {
+            return Some(&self.storage[start..start + n]);
+        }
+
+        scratch[..first_run].copy_from_slice(&self.storage[start..self.capacity]);
+        scratch[first_run..n].copy_from_slice(&self.storage[..n - first_run]);
+        Some(&scratch[..n])
+    }
+
+    /// 从读游标偏移 `start` 处开始查找单个字节 `needle`（例如 COBS 的 `0x00` 分隔符），
+    /// 返回相对读游标的偏移量
+    pub fn find_byte(&self, start: usize, needle: u8) -> Option<usize> {
+        if start >= self.len {
+            return None;
+        }
+        (start..self.len).find(|&offset| self.peek_byte(offset) == Some(needle))
+    }
+
+    /// 读游标处的单字节，便于逐字节丢弃无效数据
+    pub fn peek_byte(&self, offset: usize) -> Option<u8> {
+        if offset >= self.len {
+            return None;
+        }
+        Some(self.storage[(self.head + offset) % self.capacity])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enqueue_and_peek_contiguous() {
+        let mut rb = RingBuffer::with_capacity(8);
+        rb.enqueue(&[1, 2, 3, 4]);
+        let mut scratch = [0u8; 4];
+        assert_eq!(rb.peek(4, &mut scratch), Some(&[1, 2, 3, 4][..]));
+    }
+
+    #[test]
+    fn advance_moves_head_and_shrinks_len() {
+        let mut rb = RingBuffer::with_capacity(8);
+        rb.enqueue(&[1, 2, 3, 4]);
+        rb.advance(2);
+        assert_eq!(rb.len(), 2);
+        let mut scratch = [0u8; 2];
+        assert_eq!(rb.peek(2, &mut scratch), Some(&[3, 4][..]));
+    }
+
+    #[test]
+    fn peek_handles_wraparound() {
+        let mut rb = RingBuffer::with_capacity(4);
+        rb.enqueue(&[1, 2, 3]);
+        rb.advance(3); // head == tail == 3 now, buffer empty but cursor near the end
+        rb.enqueue(&[4, 5, 6]); // wraps: 4 -> idx3, 5 -> idx0, 6 -> idx1
+        let mut scratch = [0u8; 3];
+        assert_eq!(rb.peek(3, &mut scratch), Some(&[4, 5, 6][..]));
+    }
+
+    #[test]
+    fn find_header_across_wraparound() {
+        let mut rb = RingBuffer::with_capacity(4);
+        rb.enqueue(&[0x00, 0x00, 0x00]);
+        rb.advance(3);
+        rb.enqueue(&[0x00, 0xAA, 0x55]);
+        assert_eq!(rb.find(&[0xAA, 0x55]), Some(1));
+    }
+
+    #[test]
+    fn find_byte_locates_single_delimiter() {
+        let mut rb = RingBuffer::with_capacity(8);
+        rb.enqueue(&[1, 2, 0x00, 4]);
+        assert_eq!(rb.find_byte(0, 0x00), Some(2));
+        assert_eq!(rb.find_byte(3, 0x00), None);
+    }
+
+    #[test]
+    fn enqueue_truncates_when_full() {
+        let mut rb = RingBuffer::with_capacity(4);
+        let written = rb.enqueue(&[1, 2, 3, 4, 5]);
+        assert_eq!(written, 4);
+        assert_eq!(rb.len(), 4);
+    }
+}
@@ -0,0 +1,207 @@
+//! Unified stop-frame / fail-safe policy shared by every drive mode
+//!
+//! Before this module existed, the CSV loops built their own stop frame inline
+//! (rolling heartbeat + XOR checksum over a hardcoded CAN ID) while the infinite-drive
+//! PID loop just sent a bare zero-speed frame with no heartbeat continuity, no checksum,
+//! and no watchdog if the send channel ever stalled. [`SafetyController`] centralizes all
+//! of that: one rolling heartbeat counter, one checksum routine, and one software
+//! watchdog that fires its own stop frame if nothing has gone out within a configurable
+//! deadline - the same fail-safe braking guarantee a real driver-assist system has to
+//! provide, regardless of which drive mode is currently running.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+use log::{error, info, warn};
+use tauri::Emitter;
+
+use crate::can_protocol::{create_can_send_packet_fixed, create_can_send_packet_variable};
+use crate::{AppState, SendMessage};
+
+/// How often the watchdog thread polls [`Inner::last_sent_at`] against `watchdog_timeout`.
+const WATCHDOG_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default stop-frame watchdog deadline: if no frame has been dispatched in this long, the
+/// send channel is assumed stalled and the watchdog brakes on its own.
+fn default_watchdog_timeout() -> Duration {
+    Duration::from_millis(500)
+}
+
+struct Inner {
+    /// Rolling heartbeat nibble (0x00..=0x0F), shifted into the high nibble of the
+    /// heartbeat byte on every stop frame - capped at 0xF0, wrapping back to 0x00.
+    heartbeat: u8,
+    last_sent_at: Instant,
+}
+
+/// Builds and dispatches the shared 8-byte stop frame (`04 00 00 00 00 00 [heartbeat] [checksum]`:
+/// keep D gear, zero speed/steering, rolling heartbeat, XOR checksum of the other 7 bytes), and
+/// watches over a drive loop to brake on its own if the loop stalls.
+///
+/// Cheaply `Clone`-able (the heartbeat/watchdog state lives behind an `Arc<Mutex<_>>`) so the
+/// owning drive loop and its [`SafetyController::spawn_watchdog`] background thread share the
+/// same counter instead of racing two independent ones.
+#[derive(Clone)]
+pub struct SafetyController {
+    stop_can_id: String,
+    /// Index (0-7) of the heartbeat byte in the stop frame; the checksum always follows it.
+    heartbeat_byte_index: usize,
+    frame_type: String,
+    protocol_length: String,
+    watchdog_timeout: Duration,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl SafetyController {
+    pub fn new(
+        stop_can_id: impl Into<String>,
+        heartbeat_byte_index: usize,
+        frame_type: impl Into<String>,
+        protocol_length: impl Into<String>,
+        watchdog_timeout: Duration,
+    ) -> Self {
+        Self {
+            stop_can_id: stop_can_id.into(),
+            heartbeat_byte_index,
+            frame_type: frame_type.into(),
+            protocol_length: protocol_length.into(),
+            watchdog_timeout,
+            inner: Arc::new(Mutex::new(Inner { heartbeat: 0x00, last_sent_at: Instant::now() })),
+        }
+    }
+
+    /// Builds a controller from a drive mode's `config` blob: `stop_can_id` (defaults to
+    /// `default_stop_can_id` - the CSV loops used to hardcode `0x18C4D2D0`, infinite-drive
+    /// should pass its own `0x200`), `heartbeat_byte_index` (default 6, checksum follows at
+    /// `heartbeat_byte_index + 1`), and `watchdog_timeout_ms` (default 500).
+    pub fn from_config(
+        config: &serde_json::Value,
+        default_stop_can_id: &str,
+        frame_type: &str,
+        protocol_length: &str,
+    ) -> Self {
+        let stop_can_id = config
+            .get("stop_can_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or(default_stop_can_id)
+            .to_string();
+        let heartbeat_byte_index = config
+            .get("heartbeat_byte_index")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(6);
+        let watchdog_timeout = config
+            .get("watchdog_timeout_ms")
+            .and_then(|v| v.as_u64())
+            .map(Duration::from_millis)
+            .unwrap_or_else(default_watchdog_timeout);
+
+        Self::new(stop_can_id, heartbeat_byte_index, frame_type, protocol_length, watchdog_timeout)
+    }
+
+    /// Call after every successfully dispatched frame (stop frames included) so the watchdog
+    /// knows the channel is still alive.
+    pub fn note_sent(&self) {
+        self.inner.lock().unwrap().last_sent_at = Instant::now();
+    }
+
+    /// Builds one stop frame off the current heartbeat and advances it (capped at 0xF0,
+    /// wrapping to 0x00), the same policy [`note_sent`](Self::note_sent)-tracked callers used
+    /// to hand-roll per drive mode.
+    fn build_stop_frame(&self) -> Result<(String, Vec<u8>)> {
+        if self.heartbeat_byte_index >= 8 {
+            return Err(anyhow!("heartbeat_byte_index {} out of range (0-7)", self.heartbeat_byte_index));
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let mut bytes = [0u8; 8];
+        bytes[0] = 0x04; // Keep D gear, zero speed/steering
+
+        bytes[self.heartbeat_byte_index] = inner.heartbeat << 4;
+        inner.heartbeat = if inner.heartbeat >= 0x0F { 0x00 } else { inner.heartbeat + 1 };
+        let heartbeat_byte = bytes[self.heartbeat_byte_index];
+        drop(inner);
+
+        let checksum_index = (self.heartbeat_byte_index + 1) % bytes.len();
+        let checksum = bytes
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != checksum_index)
+            .fold(0u8, |acc, (_, &b)| acc ^ b);
+        bytes[checksum_index] = checksum;
+
+        let data = bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ");
+        info!(
+            "📤 [Rust] Built stop frame for {}: {} (heartbeat: {:02X}, checksum: {:02X})",
+            self.stop_can_id, data, heartbeat_byte, checksum
+        );
+
+        let packet = if self.protocol_length == "variable" {
+            create_can_send_packet_variable(&self.stop_can_id, &data, &self.frame_type)?
+        } else {
+            create_can_send_packet_fixed(&self.stop_can_id, &data, &self.frame_type)?
+        };
+
+        Ok((self.stop_can_id.clone(), packet))
+    }
+
+    /// Builds and enqueues one stop frame on `state.tx_send`. Called on user stop, on loop
+    /// completion, and by the watchdog on timeout - every drive mode's only way to brake.
+    pub fn send_stop_frame(&self, state: &AppState) -> Result<()> {
+        let (stop_can_id, packet) = self.build_stop_frame()?;
+        {
+            let tx_send = state.tx_send.lock().unwrap();
+            if let Some(ref queue) = *tx_send {
+                queue
+                    .enqueue_blocking(SendMessage { packet }, Duration::from_millis(50))
+                    .map_err(|e| anyhow!("Failed to send stop signal: {}", e))?;
+            }
+        }
+        self.note_sent();
+        info!("✅ [Rust] Sent stop signal - ID: {}", stop_can_id);
+        Ok(())
+    }
+
+    /// Spawns the software watchdog: polls every [`WATCHDOG_POLL_INTERVAL`] while
+    /// `running_flag` is set, and the moment nothing has been sent for `watchdog_timeout`,
+    /// fires its own stop frame, emits `"safety-watchdog-triggered"`, and clears
+    /// `running_flag` so the owning drive loop exits on its next check - fail-safe braking
+    /// for a stalled send channel, not just an explicit stop/completion.
+    pub fn spawn_watchdog(
+        &self,
+        running_flag: Arc<AtomicBool>,
+        state: Arc<AppState>,
+        app_handle: tauri::AppHandle,
+    ) -> thread::JoinHandle<()> {
+        let safety = self.clone();
+        thread::spawn(move || {
+            while running_flag.load(Ordering::SeqCst) {
+                thread::sleep(WATCHDOG_POLL_INTERVAL);
+
+                if !running_flag.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let idle = safety.inner.lock().unwrap().last_sent_at.elapsed();
+                if idle >= safety.watchdog_timeout {
+                    warn!(
+                        "[safety] watchdog: no frame sent for {:?} (deadline {:?}), braking",
+                        idle, safety.watchdog_timeout
+                    );
+                    running_flag.store(false, Ordering::SeqCst);
+                    if let Err(e) = safety.send_stop_frame(&state) {
+                        error!("[safety] watchdog stop frame failed: {}", e);
+                    }
+                    let _ = app_handle.emit(
+                        "safety-watchdog-triggered",
+                        serde_json::json!({ "idle_ms": idle.as_millis() as u64 }),
+                    );
+                    break;
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,207 @@
+//! 周期性发送调度器
+//! 基于哈希定时轮 (hashed timer wheel) 实现，让 I/O 线程无需为每个周期任务
+//! 额外开线程，也无需每个 tick 扫描全部任务。
+
+use std::collections::HashMap;
+
+/// 定时轮槽位数，每个槽代表一次 tick（默认 1ms），循环一圈约等于 1 秒
+const WHEEL_SLOTS: usize = 1000;
+
+/// 单个周期发送任务
+#[derive(Debug, Clone)]
+struct Job {
+    packet: Vec<u8>,
+    period_ticks: u64,
+    /// 还需触发多少圈（走完 WHEEL_SLOTS 个槽才算一圈）才真正到期
+    rotation: u64,
+    /// 剩余重复次数，`None` 表示无限重复
+    remaining_repeat: Option<u32>,
+}
+
+/// 提供给 UI 展示/管理的任务信息
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CyclicJobInfo {
+    pub job_id: u64,
+    pub period_ms: u64,
+    pub remaining_repeat: Option<u32>,
+}
+
+/// 哈希定时轮：任务按到期 tick 数 `d` 被放入槽 `(current + d) % WHEEL_SLOTS`，
+/// `rotation = (d - 1) / WHEEL_SLOTS` 记录还需要再转多少圈。每个 tick 只扫描当前槽，
+/// 插入/到期都是 O(1)（不计同槽任务数量）。
+pub struct TimerWheel {
+    slots: Vec<Vec<u64>>,
+    jobs: HashMap<u64, Job>,
+    current_slot: usize,
+    next_job_id: u64,
+}
+
+impl TimerWheel {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![Vec::new(); WHEEL_SLOTS],
+            jobs: HashMap::new(),
+            current_slot: 0,
+            next_job_id: 1,
+        }
+    }
+
+    /// 注册一个周期发送任务，返回任务 id
+    ///
+    /// `period_ms` 为发送周期（按 1ms 一个 tick 换算），`repeat` 为 `None` 表示无限重复，
+    /// 否则表示总共发送的次数。
+    pub fn schedule(&mut self, packet: Vec<u8>, period_ms: u64, repeat: Option<u32>) -> u64 {
+        let period_ticks = period_ms.max(1);
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+
+        let job = Job {
+            packet,
+            period_ticks,
+            rotation: 0,
+            remaining_repeat: repeat,
+        };
+        self.insert(job_id, job, period_ticks);
+        job_id
+    }
+
+    fn insert(&mut self, job_id: u64, mut job: Job, delay_ticks: u64) {
+        let slot = (self.current_slot + delay_ticks as usize) % WHEEL_SLOTS;
+        job.rotation = (delay_ticks.max(1) - 1) / WHEEL_SLOTS as u64;
+        self.slots[slot].push(job_id);
+        self.jobs.insert(job_id, job);
+    }
+
+    /// 取消一个任务；任务可能仍残留在某个槽里，到期扫描时会因为在 `jobs` 中查不到而被跳过
+    pub fn cancel(&mut self, job_id: u64) -> bool {
+        self.jobs.remove(&job_id).is_some()
+    }
+
+    /// 列出当前仍处于活跃状态的任务
+    pub fn list(&self) -> Vec<CyclicJobInfo> {
+        self.jobs
+            .iter()
+            .map(|(&job_id, job)| CyclicJobInfo {
+                job_id,
+                period_ms: job.period_ticks,
+                remaining_repeat: job.remaining_repeat,
+            })
+            .collect()
+    }
+
+    /// 推进一个 tick，返回本次到期、需要发送的数据包
+    ///
+    /// 先推进 `current_slot` 再取槽：`insert` 把延迟 `d` 的任务放进
+    /// `(current_slot + d) % WHEEL_SLOTS`，所以必须先走到那一格，延迟 `d` 才会
+    /// 精确在第 `d` 次 `tick()` 触发，而不是第 `d+1` 次。
+    pub fn tick(&mut self) -> Vec<Vec<u8>> {
+        self.current_slot = (self.current_slot + 1) % WHEEL_SLOTS;
+        let slot = self.current_slot;
+        let ids = std::mem::take(&mut self.slots[slot]);
+        let mut due = Vec::new();
+
+        for job_id in ids {
+            let Some(mut job) = self.jobs.remove(&job_id) else {
+                // 任务已被取消，丢弃这个陈旧的槽位记录
+                continue;
+            };
+
+            if job.rotation > 0 {
+                job.rotation -= 1;
+                self.slots[slot].push(job_id);
+                self.jobs.insert(job_id, job);
+                continue;
+            }
+
+            due.push(job.packet.clone());
+
+            let keep_running = match &mut job.remaining_repeat {
+                Some(remaining) => {
+                    *remaining -= 1;
+                    *remaining > 0
+                }
+                None => true,
+            };
+
+            if keep_running {
+                let period_ticks = job.period_ticks;
+                self.insert(job_id, job, period_ticks);
+            }
+        }
+
+        due
+    }
+}
+
+impl Default for TimerWheel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_job_after_period_ticks() {
+        let mut wheel = TimerWheel::new();
+        wheel.schedule(vec![0xAA], 3, Some(1));
+
+        for _ in 0..2 {
+            assert!(wheel.tick().is_empty());
+        }
+        assert_eq!(wheel.tick(), vec![vec![0xAA]]);
+    }
+
+    #[test]
+    fn periodic_job_fires_repeatedly() {
+        let mut wheel = TimerWheel::new();
+        wheel.schedule(vec![0x01], 2, None);
+
+        let mut fired = 0;
+        for _ in 0..10 {
+            fired += wheel.tick().len();
+        }
+        assert_eq!(fired, 5);
+    }
+
+    #[test]
+    fn cancel_stops_future_firings() {
+        let mut wheel = TimerWheel::new();
+        let job_id = wheel.schedule(vec![0x02], 1, None);
+
+        assert_eq!(wheel.tick().len(), 1);
+        assert!(wheel.cancel(job_id));
+
+        for _ in 0..5 {
+            assert!(wheel.tick().is_empty());
+        }
+    }
+
+    #[test]
+    fn job_spanning_multiple_wheel_rotations() {
+        let mut wheel = TimerWheel::new();
+        // Period longer than WHEEL_SLOTS forces rotation > 0
+        wheel.schedule(vec![0x03], WHEEL_SLOTS as u64 + 5, Some(1));
+
+        let mut fired_at = None;
+        for i in 0..(WHEEL_SLOTS + 10) {
+            if !wheel.tick().is_empty() {
+                fired_at = Some(i);
+                break;
+            }
+        }
+        assert_eq!(fired_at, Some(WHEEL_SLOTS + 5 - 1));
+    }
+
+    #[test]
+    fn list_reflects_active_jobs() {
+        let mut wheel = TimerWheel::new();
+        let id = wheel.schedule(vec![0x04], 20, Some(3));
+        let jobs = wheel.list();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].job_id, id);
+        assert_eq!(jobs[0].remaining_repeat, Some(3));
+    }
+}
@@ -0,0 +1,404 @@
+//! SCPI 风格的文本指令控制台
+//!
+//! 一次性的 `send_can_message` 和批量回放的 CSV 循环之外，熟悉仪器控制指令的用户
+//! 想直接敲命令跑一段可复用的测试序列。语法借鉴示波器/电源这类设备常见的 SCPI
+//! 逐行指令：每行一个动词加参数，不区分大小写，支持 `REPEAT n ... ENDREPEAT`
+//! 嵌套循环体。解析成一棵指令树后交给独立线程执行，通过 `AppState::script_running`
+//! 响应中止请求，复用既有的 `tx_send` 有界队列发送数据帧。
+//!
+//! 支持的指令：
+//! - `SEND <id> <data> [frame_type]`           发送一帧 CAN 消息
+//! - `DELAY <ms>`                              等待指定毫秒数
+//! - `REPEAT <n>` ... `ENDREPEAT`              把中间的指令重复执行 n 次
+//! - `CONFIG baud <bps>`                       重新发送一次 CAN 配置包，覆盖波特率
+//! - `WAITFOR <id> <timeout_ms>`               等待收到指定 ID 的帧，超时则记为失败
+
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::Serialize;
+use tauri::{Emitter, Listener};
+
+use crate::can_protocol::{
+    create_can_config_packet, create_can_send_packet_fixed, create_can_send_packet_variable,
+};
+use crate::{AppState, SendMessage, SerialConfig};
+
+/// 解析后的单条脚本指令
+#[derive(Debug, Clone)]
+pub(crate) enum ScriptCommand {
+    Send {
+        id: String,
+        data: String,
+        frame_type: String,
+    },
+    Delay(u64),
+    Repeat {
+        count: u32,
+        body: Vec<ScriptCommand>,
+    },
+    Config {
+        baud: u32,
+    },
+    WaitFor {
+        id: String,
+        timeout_ms: u64,
+    },
+}
+
+/// 执行过程中向前端汇报的单行进度，呼应 CSV 循环的 `CsvLoopProgress`
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptLineProgress {
+    pub line: usize,
+    pub total_lines: usize,
+    pub command: String,
+    /// "running" | "ok" | "error" | "timeout"
+    pub status: String,
+    pub detail: String,
+}
+
+/// 把脚本文本解析成一棵指令树；空行和 `#` 开头的注释行会被忽略
+pub fn parse_script(text: &str) -> Result<Vec<ScriptCommand>> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+
+    let mut cursor = 0usize;
+    let commands = parse_block(&lines, &mut cursor)?;
+    if cursor != lines.len() {
+        return Err(anyhow!("Unmatched ENDREPEAT near line {}", cursor + 1));
+    }
+    Ok(commands)
+}
+
+/// 解析一段指令列表，遇到 `ENDREPEAT`（而不消费它）或数据耗尽就停下，
+/// 由调用方（顶层 `parse_script` 或 `REPEAT` 分支）决定接下来怎么处理游标
+fn parse_block(lines: &[&str], cursor: &mut usize) -> Result<Vec<ScriptCommand>> {
+    let mut commands = Vec::new();
+    while *cursor < lines.len() {
+        if lines[*cursor].eq_ignore_ascii_case("ENDREPEAT") {
+            break;
+        }
+        let line = lines[*cursor];
+        *cursor += 1;
+        commands.push(parse_line(line, lines, cursor)?);
+    }
+    Ok(commands)
+}
+
+fn parse_line(line: &str, lines: &[&str], cursor: &mut usize) -> Result<ScriptCommand> {
+    let mut parts = line.split_whitespace();
+    let verb = parts
+        .next()
+        .ok_or_else(|| anyhow!("Empty script line"))?
+        .to_ascii_uppercase();
+
+    match verb.as_str() {
+        "SEND" => {
+            let id = parts
+                .next()
+                .ok_or_else(|| anyhow!("SEND missing CAN id: '{}'", line))?
+                .to_string();
+            let data = parts
+                .next()
+                .ok_or_else(|| anyhow!("SEND missing data: '{}'", line))?
+                .to_string();
+            let frame_type = parts.next().unwrap_or("standard").to_string();
+            Ok(ScriptCommand::Send {
+                id,
+                data,
+                frame_type,
+            })
+        }
+        "DELAY" => {
+            let ms = parts
+                .next()
+                .ok_or_else(|| anyhow!("DELAY missing duration: '{}'", line))?
+                .parse::<u64>()
+                .map_err(|_| anyhow!("DELAY duration must be an integer (ms): '{}'", line))?;
+            Ok(ScriptCommand::Delay(ms))
+        }
+        "REPEAT" => {
+            let count = parts
+                .next()
+                .ok_or_else(|| anyhow!("REPEAT missing count: '{}'", line))?
+                .parse::<u32>()
+                .map_err(|_| anyhow!("REPEAT count must be an integer: '{}'", line))?;
+            let body = parse_block(lines, cursor)?;
+            if *cursor >= lines.len() {
+                return Err(anyhow!("REPEAT without matching ENDREPEAT: '{}'", line));
+            }
+            *cursor += 1; // consume the ENDREPEAT line itself
+            Ok(ScriptCommand::Repeat { count, body })
+        }
+        "CONFIG" => {
+            let key = parts
+                .next()
+                .ok_or_else(|| anyhow!("CONFIG missing key: '{}'", line))?
+                .to_ascii_lowercase();
+            if key != "baud" {
+                return Err(anyhow!("Unsupported CONFIG key '{}', only 'baud' is supported", key));
+            }
+            let baud = parts
+                .next()
+                .ok_or_else(|| anyhow!("CONFIG baud missing value: '{}'", line))?
+                .parse::<u32>()
+                .map_err(|_| anyhow!("CONFIG baud must be an integer: '{}'", line))?;
+            Ok(ScriptCommand::Config { baud })
+        }
+        "WAITFOR" => {
+            let id = parts
+                .next()
+                .ok_or_else(|| anyhow!("WAITFOR missing CAN id: '{}'", line))?
+                .to_string();
+            let timeout_ms = parts
+                .next()
+                .ok_or_else(|| anyhow!("WAITFOR missing timeout: '{}'", line))?
+                .parse::<u64>()
+                .map_err(|_| anyhow!("WAITFOR timeout must be an integer (ms): '{}'", line))?;
+            Ok(ScriptCommand::WaitFor { id, timeout_ms })
+        }
+        other => Err(anyhow!("Unknown script verb '{}' in line '{}'", other, line)),
+    }
+}
+
+/// 在独立线程里跑完一棵解析好的指令树；`config` 用于按协议长度/波特率重建发送包，
+/// 和连接时用的是同一份设置。每执行一行就发一次 `"script-line-progress"` 事件，
+/// 跑完发一次 `"script-completed"`。`state.script_running` 随时可能被 `stop_script`
+/// 清掉，每条指令、每次 REPEAT 迭代之间都会检查一次。
+pub fn run_script(
+    commands: Vec<ScriptCommand>,
+    config: SerialConfig,
+    state: AppState,
+    app_handle: tauri::AppHandle,
+) {
+    state.script_running.store(true, Ordering::SeqCst);
+    let total_lines = commands.len();
+
+    thread::spawn(move || {
+        info!("Script: execution started, {} top-level command(s)", total_lines);
+
+        for (index, command) in commands.iter().enumerate() {
+            if !state.script_running.load(Ordering::SeqCst) {
+                info!("Script: aborted by user");
+                break;
+            }
+            execute_command(command, index, total_lines, &config, &state, &app_handle);
+        }
+
+        state.script_running.store(false, Ordering::SeqCst);
+        let _ = app_handle.emit(
+            "script-completed",
+            serde_json::json!({ "status": "completed" }),
+        );
+        info!("Script: execution finished");
+    });
+}
+
+fn execute_command(
+    command: &ScriptCommand,
+    index: usize,
+    total_lines: usize,
+    config: &SerialConfig,
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+) {
+    if !state.script_running.load(Ordering::SeqCst) {
+        return;
+    }
+
+    match command {
+        ScriptCommand::Send {
+            id,
+            data,
+            frame_type,
+        } => {
+            emit_progress(app_handle, index, total_lines, "SEND", "running", "");
+            let packet_result = if config.protocol_length == "variable" {
+                create_can_send_packet_variable(id, data, frame_type)
+            } else {
+                create_can_send_packet_fixed(id, data, frame_type)
+            };
+            match packet_result.map_err(|e| e.to_string()).and_then(|packet| send_packet(state, packet)) {
+                Ok(()) => emit_progress(app_handle, index, total_lines, "SEND", "ok", ""),
+                Err(e) => emit_progress(app_handle, index, total_lines, "SEND", "error", &e),
+            }
+        }
+        ScriptCommand::Delay(ms) => {
+            emit_progress(
+                app_handle,
+                index,
+                total_lines,
+                "DELAY",
+                "running",
+                &format!("{}ms", ms),
+            );
+            thread::sleep(Duration::from_millis(*ms));
+            emit_progress(app_handle, index, total_lines, "DELAY", "ok", "");
+        }
+        ScriptCommand::Repeat { count, body } => {
+            for iteration in 0..*count {
+                if !state.script_running.load(Ordering::SeqCst) {
+                    break;
+                }
+                emit_progress(
+                    app_handle,
+                    index,
+                    total_lines,
+                    "REPEAT",
+                    "running",
+                    &format!("iteration {}/{}", iteration + 1, count),
+                );
+                for (sub_index, sub_command) in body.iter().enumerate() {
+                    if !state.script_running.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    execute_command(sub_command, sub_index, body.len(), config, state, app_handle);
+                }
+            }
+        }
+        ScriptCommand::Config { baud } => {
+            emit_progress(
+                app_handle,
+                index,
+                total_lines,
+                "CONFIG",
+                "running",
+                &format!("baud {}", baud),
+            );
+            let mut updated = config.clone();
+            updated.can_baud_rate = *baud;
+            let packet = create_can_config_packet(&updated);
+            match send_packet(state, packet) {
+                Ok(()) => emit_progress(app_handle, index, total_lines, "CONFIG", "ok", ""),
+                Err(e) => emit_progress(app_handle, index, total_lines, "CONFIG", "error", &e),
+            }
+        }
+        ScriptCommand::WaitFor { id, timeout_ms } => {
+            emit_progress(
+                app_handle,
+                index,
+                total_lines,
+                "WAITFOR",
+                "running",
+                &format!("{} within {}ms", id, timeout_ms),
+            );
+            if wait_for_frame(app_handle, id, *timeout_ms) {
+                emit_progress(app_handle, index, total_lines, "WAITFOR", "ok", "");
+            } else {
+                emit_progress(app_handle, index, total_lines, "WAITFOR", "timeout", "");
+            }
+        }
+    }
+}
+
+/// 发一次 `"script-line-progress"` 事件，呼应 CSV 循环的进度事件
+fn emit_progress(
+    app_handle: &tauri::AppHandle,
+    line: usize,
+    total_lines: usize,
+    command: &str,
+    status: &str,
+    detail: &str,
+) {
+    let progress = ScriptLineProgress {
+        line,
+        total_lines,
+        command: command.to_string(),
+        status: status.to_string(),
+        detail: detail.to_string(),
+    };
+    let _ = app_handle.emit("script-line-progress", progress);
+}
+
+fn send_packet(state: &AppState, packet: Vec<u8>) -> Result<(), String> {
+    let tx_send = state.tx_send.lock().unwrap();
+    match *tx_send {
+        Some(ref queue) => queue.try_enqueue(SendMessage { packet }).map_err(|e| e.to_string()),
+        None => Err("Send channel not available".to_string()),
+    }
+}
+
+/// 监听一次性的 `"can-message-received"` 事件，直到收到匹配 `expected_id` 的帧或超时
+fn wait_for_frame(app_handle: &tauri::AppHandle, expected_id: &str, timeout_ms: u64) -> bool {
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    let expected = normalize_can_id(expected_id);
+
+    let listener_id = app_handle.listen("can-message-received", move |event| {
+        let Ok(can_message) = serde_json::from_str::<serde_json::Value>(event.payload()) else {
+            return;
+        };
+        let matches = can_message
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(normalize_can_id)
+            == Some(expected.clone());
+        if matches {
+            let _ = tx.send(());
+        }
+    });
+
+    let matched = rx.recv_timeout(Duration::from_millis(timeout_ms)).is_ok();
+    app_handle.unlisten(listener_id);
+    matched
+}
+
+fn normalize_can_id(id: &str) -> String {
+    id.trim().to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flat_sequence() {
+        let script = "SEND 123 0011223344556677 standard\nDELAY 250\nWAITFOR 0x123 500";
+        let commands = parse_script(script).unwrap();
+        assert_eq!(commands.len(), 3);
+        assert!(matches!(commands[0], ScriptCommand::Send { .. }));
+        assert!(matches!(commands[1], ScriptCommand::Delay(250)));
+        assert!(matches!(commands[2], ScriptCommand::WaitFor { timeout_ms: 500, .. }));
+    }
+
+    #[test]
+    fn parses_nested_repeat_block() {
+        let script = "REPEAT 3\nSEND 123 00 standard\nDELAY 10\nENDREPEAT";
+        let commands = parse_script(script).unwrap();
+        assert_eq!(commands.len(), 1);
+        match &commands[0] {
+            ScriptCommand::Repeat { count, body } => {
+                assert_eq!(*count, 3);
+                assert_eq!(body.len(), 2);
+            }
+            other => panic!("expected Repeat, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_unmatched_endrepeat() {
+        assert!(parse_script("ENDREPEAT").is_err());
+    }
+
+    #[test]
+    fn rejects_repeat_without_endrepeat() {
+        assert!(parse_script("REPEAT 2\nSEND 123 00").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_verb() {
+        assert!(parse_script("FROB 1 2 3").is_err());
+    }
+
+    #[test]
+    fn ignores_blank_lines_and_comments() {
+        let script = "# a comment\n\nDELAY 5\n";
+        let commands = parse_script(script).unwrap();
+        assert_eq!(commands.len(), 1);
+    }
+}
@@ -0,0 +1,622 @@
+//! CAN 信号数据库
+//! 将 ID -> 信号列表的解码规则表格化，替代分散在各处的硬编码解析函数。
+//! 每个信号描述起始位、位长、字节序、缩放/偏移量以及可选的枚举值表，
+//! 解码时按 `physical = raw * scale + offset` 还原物理量。
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::can_frame::CanFrame;
+
+/// 信号在 8 字节数据域中的字节序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Intel 风格，小端序
+    LittleEndian,
+    /// Motorola 风格，大端序
+    BigEndian,
+}
+
+/// 单个信号的解码描述
+#[derive(Debug, Clone)]
+pub struct SignalDef {
+    pub name: String,
+    /// 这个信号所属的 CAN ID；[`SignalDatabase`] 内部按 ID 分组存放在 `MessageDef`
+    /// 里，但 [`decode`] 需要接受一份零散的 `&[SignalDef]`（不一定来自某个已注册的
+    /// 数据库），所以每个信号自带归属的 ID，靠它在解码时按 `frame.id` 过滤
+    pub can_id: u32,
+    /// 起始位（小端序时为最低位的位号，大端序时为最高位的位号），0 起始
+    pub start_bit: u8,
+    pub length_bits: u8,
+    pub byte_order: ByteOrder,
+    /// 是否按补码做符号扩展；为 false 时原始整数按无符号处理
+    pub signed: bool,
+    pub scale: f64,
+    pub offset: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub unit: String,
+    /// 可选的枚举值表（原始整数值 -> 名称），用于档位等场景
+    pub enum_values: Option<HashMap<i64, String>>,
+}
+
+/// 一条 CAN 消息下的信号集合
+#[derive(Debug, Clone, Default)]
+pub struct MessageDef {
+    pub signals: Vec<SignalDef>,
+}
+
+/// 解码后的单个信号结果
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DecodedSignal {
+    pub name: String,
+    pub value: serde_json::Value,
+    pub unit: String,
+}
+
+/// ID -> 消息定义 的信号数据库
+#[derive(Debug, Clone, Default)]
+pub struct SignalDatabase {
+    messages: HashMap<u32, MessageDef>,
+}
+
+impl SignalDatabase {
+    pub fn new() -> Self {
+        Self {
+            messages: HashMap::new(),
+        }
+    }
+
+    /// 注册/覆盖一条消息的信号定义
+    pub fn register(&mut self, can_id: u32, message: MessageDef) {
+        self.messages.insert(can_id, message);
+    }
+
+    /// 从 JSON 描述加载信号数据库，便于用户在不重新编译的情况下新增 ECU
+    ///
+    /// 期望格式：`{ "0x123": [ {"name": "...", "start_bit": 0, "length_bits": 4,
+    /// "byte_order": "little", "scale": 1.0, "offset": 0.0, "unit": "mm/s"} ] }`
+    pub fn load_from_json(json: &str) -> anyhow::Result<Self> {
+        let raw: HashMap<String, Vec<SignalJson>> = serde_json::from_str(json)?;
+        let mut db = Self::new();
+
+        for (id_str, signals) in raw {
+            let id_hex_part = id_str
+                .strip_prefix("0x")
+                .or_else(|| id_str.strip_prefix("0X"))
+                .unwrap_or(&id_str);
+            let can_id = u32::from_str_radix(id_hex_part, 16)
+                .map_err(|_| anyhow::anyhow!("Invalid CAN ID in signal database: {}", id_str))?;
+
+            let signal_defs = signals
+                .into_iter()
+                .map(|raw| raw.into_signal_def(can_id))
+                .collect();
+            db.register(can_id, MessageDef { signals: signal_defs });
+        }
+
+        Ok(db)
+    }
+
+    /// 解码给定 ID 的 8 字节数据，返回表中定义的每个信号
+    pub fn decode(&self, can_id: u32, data: &[u8]) -> Vec<DecodedSignal> {
+        let Some(message) = self.messages.get(&can_id) else {
+            return Vec::new();
+        };
+
+        message
+            .signals
+            .iter()
+            .filter_map(|signal| signal.decode(data))
+            .collect()
+    }
+
+    /// DBC 风格的精简解码：跳过 `enum_values`，只返回 (信号名, 物理量, 单位) 三元组。
+    /// 帧长不够覆盖某个信号的位区间时跳过该信号并打印告警，不影响其它信号的解码。
+    pub fn decode_frame(&self, can_id: u32, data: &[u8]) -> Vec<(String, f64, String)> {
+        let Some(message) = self.messages.get(&can_id) else {
+            return Vec::new();
+        };
+
+        message
+            .signals
+            .iter()
+            .filter_map(|signal| match signal.physical_value(data) {
+                Some(physical) => Some((signal.name.clone(), physical, signal.unit.clone())),
+                None => {
+                    log::warn!(
+                        "signal_db: signal '{}' (can_id=0x{:X}) doesn't fit in a {}-byte frame (start_bit={}, length_bits={})",
+                        signal.name,
+                        can_id,
+                        data.len(),
+                        signal.start_bit,
+                        signal.length_bits,
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// 进程内置的默认信号表，覆盖此前硬编码在 `handle_parsed_can_message` /
+    /// `parse_vehicle_control_data` 里的 ID：车辆状态 (0x123) 与雷达距离 (0x521-0x524)
+    pub fn global() -> &'static SignalDatabase {
+        static DB: OnceLock<SignalDatabase> = OnceLock::new();
+        DB.get_or_init(Self::with_builtin_defaults)
+    }
+
+    fn with_builtin_defaults() -> Self {
+        let mut db = Self::new();
+        for def in built_in_defs() {
+            db.messages.entry(def.can_id).or_default().signals.push(def);
+        }
+        db
+    }
+}
+
+/// 摊平成 `Vec<SignalDef>` 的内置信号表，原样保留此前硬编码在
+/// `parse_vehicle_status_8byte`（档位/车速/转向角，ID 0x123）和
+/// `parse_distance_from_data`（雷达距离，ID 0x521-0x524）里的偏移量/缩放/档位枚举，
+/// 行为不变；用户可以在这份表后面拼上自己的 [`SignalDef`] 再传给 [`decode`]，
+/// 不用改这个 crate。[`with_builtin_defaults`](SignalDatabase::with_builtin_defaults)
+/// 也是靠按 `can_id` 分组这份同一张表构建的，两条路径不会慢慢分叉
+pub fn built_in_defs() -> Vec<SignalDef> {
+    let mut gear_enum = HashMap::new();
+    gear_enum.insert(0x00, "disable".to_string());
+    gear_enum.insert(0x01, "P".to_string());
+    gear_enum.insert(0x02, "R".to_string());
+    gear_enum.insert(0x03, "N".to_string());
+    gear_enum.insert(0x04, "D".to_string());
+
+    let mut defs = vec![
+        SignalDef {
+            name: "gear".to_string(),
+            can_id: 0x00000123,
+            start_bit: 0,
+            length_bits: 4,
+            byte_order: ByteOrder::LittleEndian,
+            signed: false,
+            scale: 1.0,
+            offset: 0.0,
+            min: None,
+            max: None,
+            unit: "".to_string(),
+            enum_values: Some(gear_enum),
+        },
+        SignalDef {
+            name: "target_speed".to_string(),
+            can_id: 0x00000123,
+            start_bit: 4,
+            length_bits: 12,
+            byte_order: ByteOrder::LittleEndian,
+            signed: false,
+            scale: 0.001,
+            offset: 0.0,
+            min: None,
+            max: None,
+            unit: "m/s".to_string(),
+            enum_values: None,
+        },
+        SignalDef {
+            name: "steering_angle".to_string(),
+            can_id: 0x00000123,
+            start_bit: 16,
+            length_bits: 16,
+            byte_order: ByteOrder::LittleEndian,
+            signed: true,
+            scale: 0.01,
+            offset: 0.0,
+            min: None,
+            max: None,
+            unit: "deg".to_string(),
+            enum_values: None,
+        },
+    ];
+
+    for radar_id in [0x521u32, 0x522, 0x523, 0x524] {
+        defs.push(SignalDef {
+            name: "distance".to_string(),
+            can_id: radar_id,
+            start_bit: 48,
+            length_bits: 16,
+            byte_order: ByteOrder::BigEndian,
+            signed: false,
+            scale: 1.0,
+            offset: 0.0,
+            min: None,
+            max: None,
+            unit: "mm".to_string(),
+            enum_values: None,
+        });
+    }
+
+    defs
+}
+
+/// 按信号定义表批量解码一帧：和 [`SignalDatabase::decode`] 的区别是不需要先注册到
+/// 数据库里，可以直接传一份零散的 `&[SignalDef]`（比如 [`built_in_defs`] 拼上调用方
+/// 自己追加的信号），按 `def.can_id == frame.id` 过滤后解码，用 `HashMap` 按信号名
+/// 索引。枚举信号在 `value_map`（即 [`SignalDef::enum_values`]）里找不到对应的原始值
+/// 时，这里直接给出原始数值本身，而不是 [`SignalDef::decode`] 那种 `"unknown(n)"`
+/// 占位字符串——调用方更容易拿数值做后续判断
+pub fn decode(frame: &CanFrame, defs: &[SignalDef]) -> HashMap<String, DecodedSignal> {
+    defs.iter()
+        .filter(|def| def.can_id == frame.id)
+        .filter_map(|def| {
+            def.decode_to_raw_fallback(&frame.data)
+                .map(|value| (def.name.clone(), value))
+        })
+        .collect()
+}
+
+impl SignalDef {
+    /// 提取原始整数并按 `signed` 做符号扩展；帧长不够覆盖该信号时返回 `None`
+    fn raw_value(&self, data: &[u8]) -> Option<i64> {
+        let raw = extract_bits(data, self.start_bit, self.length_bits, self.byte_order)?;
+        Some(sign_extend(raw, self.length_bits, self.signed))
+    }
+
+    /// 按 `physical = raw * scale + offset` 换算并做 min/max 钳位，忽略 `enum_values`
+    fn physical_value(&self, data: &[u8]) -> Option<f64> {
+        let raw = self.raw_value(data)?;
+        let physical = raw as f64 * self.scale + self.offset;
+        Some(match (self.min, self.max) {
+            (Some(min), _) if physical < min => min,
+            (_, Some(max)) if physical > max => max,
+            _ => physical,
+        })
+    }
+
+    fn decode(&self, data: &[u8]) -> Option<DecodedSignal> {
+        if let Some(enum_values) = &self.enum_values {
+            let raw = self.raw_value(data)?;
+            let name = enum_values
+                .get(&raw)
+                .cloned()
+                .unwrap_or_else(|| format!("unknown({})", raw));
+            return Some(DecodedSignal {
+                name: self.name.clone(),
+                value: serde_json::json!(name),
+                unit: self.unit.clone(),
+            });
+        }
+
+        let physical = self.physical_value(data)?;
+
+        Some(DecodedSignal {
+            name: self.name.clone(),
+            value: serde_json::json!(physical),
+            unit: self.unit.clone(),
+        })
+    }
+
+    /// [`decode`]（自由函数）用的版本：枚举信号命中不到 `enum_values` 时给原始数值，
+    /// 不是 [`SignalDef::decode`] 的 `"unknown(n)"` 占位字符串
+    fn decode_to_raw_fallback(&self, data: &[u8]) -> Option<DecodedSignal> {
+        if let Some(enum_values) = &self.enum_values {
+            let raw = self.raw_value(data)?;
+            let value = match enum_values.get(&raw) {
+                Some(name) => serde_json::json!(name),
+                None => serde_json::json!(raw),
+            };
+            return Some(DecodedSignal {
+                name: self.name.clone(),
+                value,
+                unit: self.unit.clone(),
+            });
+        }
+
+        let physical = self.physical_value(data)?;
+        Some(DecodedSignal {
+            name: self.name.clone(),
+            value: serde_json::json!(physical),
+            unit: self.unit.clone(),
+        })
+    }
+}
+
+/// 按 `length_bits`/`signed` 对 [`extract_bits`] 取出的无符号整数做补码符号扩展
+fn sign_extend(raw: u64, length_bits: u8, signed: bool) -> i64 {
+    if !signed || length_bits == 0 || length_bits >= 64 {
+        return raw as i64;
+    }
+
+    let sign_bit = 1u64 << (length_bits - 1);
+    if raw & sign_bit != 0 {
+        (raw | (!0u64 << length_bits)) as i64
+    } else {
+        raw as i64
+    }
+}
+
+/// 从 8 字节数据域中按字节序提取 `length_bits` 位，组装为 u64
+fn extract_bits(data: &[u8], start_bit: u8, length_bits: u8, byte_order: ByteOrder) -> Option<u64> {
+    if length_bits == 0 || length_bits > 64 {
+        return None;
+    }
+
+    let total_bits = data.len() as u16 * 8;
+    if start_bit as u16 + length_bits as u16 > total_bits {
+        return None;
+    }
+
+    let mut value: u64 = 0;
+    match byte_order {
+        ByteOrder::LittleEndian => {
+            for i in 0..length_bits as u16 {
+                let bit_index = start_bit as u16 + i;
+                let byte = data[(bit_index / 8) as usize];
+                let bit = (byte >> (bit_index % 8)) & 1;
+                value |= (bit as u64) << i;
+            }
+        }
+        ByteOrder::BigEndian => {
+            // start_bit 视为最高位，按 Motorola 位编号从高位向低位依次取出
+            for i in 0..length_bits as u16 {
+                let bit_index = start_bit as u16 + i;
+                let byte = data[(bit_index / 8) as usize];
+                let bit = (byte >> (7 - (bit_index % 8))) & 1;
+                value = (value << 1) | bit as u64;
+            }
+        }
+    }
+
+    Some(value)
+}
+
+/// 用于从 JSON 反序列化的中间表示
+#[derive(serde::Deserialize)]
+struct SignalJson {
+    name: String,
+    start_bit: u8,
+    length_bits: u8,
+    #[serde(default = "default_byte_order")]
+    byte_order: String,
+    #[serde(default)]
+    signed: bool,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    offset: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+    #[serde(default)]
+    unit: String,
+    #[serde(default)]
+    enum_values: Option<HashMap<i64, String>>,
+}
+
+fn default_byte_order() -> String {
+    "little".to_string()
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl SignalJson {
+    /// `can_id` 来自外层 JSON 对象的 key（`load_from_json` 按 `{"0x123": [...]}`
+    /// 解析），每个信号本身的 JSON 里不重复这个字段
+    fn into_signal_def(self, can_id: u32) -> SignalDef {
+        let byte_order = if self.byte_order.eq_ignore_ascii_case("big") {
+            ByteOrder::BigEndian
+        } else {
+            ByteOrder::LittleEndian
+        };
+
+        SignalDef {
+            name: self.name,
+            can_id,
+            start_bit: self.start_bit,
+            length_bits: self.length_bits,
+            byte_order,
+            signed: self.signed,
+            scale: self.scale,
+            offset: self.offset,
+            min: self.min,
+            max: self.max,
+            unit: self.unit,
+            enum_values: self.enum_values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_builtin_gear_and_speed() {
+        let db = SignalDatabase::with_builtin_defaults();
+        // byte0 low nibble = 0x04 (D), speed raw = (byte0 high nibble) | (byte1 << 4)
+        let data = [0x04, 0x4B, 0x40, 0x01, 0x00, 0x00, 0x00, 0x2B];
+        let signals = db.decode(0x00000123, &data);
+
+        let gear = signals.iter().find(|s| s.name == "gear").unwrap();
+        assert_eq!(gear.value, serde_json::json!("D"));
+    }
+
+    #[test]
+    fn decodes_radar_distance_big_endian() {
+        let db = SignalDatabase::with_builtin_defaults();
+        let data = [0x01, 0x83, 0x02, 0xF2, 0x00, 0x00, 0x07, 0x08];
+        let signals = db.decode(0x521, &data);
+
+        let distance = signals.iter().find(|s| s.name == "distance").unwrap();
+        assert_eq!(distance.value, serde_json::json!(0x0708 as f64));
+    }
+
+    #[test]
+    fn unknown_id_has_no_signals() {
+        let db = SignalDatabase::with_builtin_defaults();
+        let data = [0u8; 8];
+        assert!(db.decode(0xDEAD, &data).is_empty());
+    }
+
+    #[test]
+    fn sign_extends_negative_signed_signal() {
+        // steering_angle: start_bit=16, length_bits=16, little-endian, signed, scale=0.01
+        // raw 16 bits = 0xFF9C = -100 -> -1.00 deg
+        let data = [0x00, 0x00, 0x9C, 0xFF, 0x00, 0x00, 0x00, 0x00];
+        let db = SignalDatabase::with_builtin_defaults();
+        let signals = db.decode(0x00000123, &data);
+
+        let angle = signals.iter().find(|s| s.name == "steering_angle").unwrap();
+        assert_eq!(angle.value, serde_json::json!(-1.0));
+    }
+
+    #[test]
+    fn decode_frame_skips_signal_that_overruns_a_short_frame() {
+        let mut db = SignalDatabase::new();
+        db.register(
+            0x400,
+            MessageDef {
+                signals: vec![
+                    SignalDef {
+                        name: "short_ok".to_string(),
+                        can_id: 0x400,
+                        start_bit: 0,
+                        length_bits: 8,
+                        byte_order: ByteOrder::LittleEndian,
+                        signed: false,
+                        scale: 1.0,
+                        offset: 0.0,
+                        min: None,
+                        max: None,
+                        unit: "".to_string(),
+                        enum_values: None,
+                    },
+                    SignalDef {
+                        name: "too_long".to_string(),
+                        can_id: 0x400,
+                        start_bit: 0,
+                        length_bits: 32,
+                        byte_order: ByteOrder::LittleEndian,
+                        signed: false,
+                        scale: 1.0,
+                        offset: 0.0,
+                        min: None,
+                        max: None,
+                        unit: "".to_string(),
+                        enum_values: None,
+                    },
+                ],
+            },
+        );
+
+        let frame = db.decode_frame(0x400, &[0x2A]);
+        assert_eq!(frame, vec![("short_ok".to_string(), 42.0, "".to_string())]);
+    }
+
+    #[test]
+    fn load_from_json_parses_custom_ecu() {
+        let json = r#"{
+            "0x300": [
+                {"name": "rpm", "start_bit": 0, "length_bits": 16, "byte_order": "little", "scale": 0.25, "unit": "rpm"}
+            ]
+        }"#;
+        let db = SignalDatabase::load_from_json(json).unwrap();
+        let mut data = [0u8; 8];
+        data[0] = 0x10;
+        data[1] = 0x27; // raw = 0x2710 = 10000 -> 2500.0 rpm
+        let signals = db.decode(0x300, &data);
+        assert_eq!(signals[0].name, "rpm");
+        assert_eq!(signals[0].value, serde_json::json!(2500.0));
+    }
+
+    #[test]
+    fn decode_filters_defs_by_frame_id_and_reproduces_vehicle_status() {
+        let frame = CanFrame {
+            id: 0x00000123,
+            extended: false,
+            fd: false,
+            remote: false,
+            data: vec![0x04, 0x4B, 0x40, 0x01, 0x00, 0x00, 0x00, 0x2B],
+            header: None,
+        };
+        let defs = built_in_defs();
+
+        let signals = decode(&frame, &defs);
+
+        assert_eq!(signals.len(), 3);
+        assert_eq!(signals["gear"].value, serde_json::json!("D"));
+        assert_eq!(signals["steering_angle"].value, serde_json::json!(3.2));
+        // 雷达信号不属于这个 ID，不应该出现在结果里
+        assert!(!signals.contains_key("distance"));
+    }
+
+    #[test]
+    fn decode_falls_back_to_raw_number_for_unmapped_enum_value() {
+        let mut enum_values = HashMap::new();
+        enum_values.insert(1i64, "on".to_string());
+
+        let def = SignalDef {
+            name: "mode".to_string(),
+            can_id: 0x700,
+            start_bit: 0,
+            length_bits: 4,
+            byte_order: ByteOrder::LittleEndian,
+            signed: false,
+            scale: 1.0,
+            offset: 0.0,
+            min: None,
+            max: None,
+            unit: "".to_string(),
+            enum_values: Some(enum_values),
+        };
+        let frame = CanFrame {
+            id: 0x700,
+            extended: false,
+            fd: false,
+            remote: false,
+            data: vec![0x09, 0, 0, 0, 0, 0, 0, 0],
+            header: None,
+        };
+
+        let signals = decode(&frame, &[def]);
+
+        // raw nibble = 9, not present in the map -> raw number, not an "unknown(9)" string
+        assert_eq!(signals["mode"].value, serde_json::json!(9));
+    }
+
+    #[test]
+    fn decode_handles_signal_straddling_a_byte_boundary_with_non_multiple_of_8_length() {
+        // start_bit=4, length_bits=12 -> straddles byte 0/byte 1, not 8/16/24-bit aligned
+        let def = SignalDef {
+            name: "straddling".to_string(),
+            can_id: 0x701,
+            start_bit: 4,
+            length_bits: 12,
+            byte_order: ByteOrder::LittleEndian,
+            signed: false,
+            scale: 1.0,
+            offset: 0.0,
+            min: None,
+            max: None,
+            unit: "".to_string(),
+            enum_values: None,
+        };
+        // byte0=0x0F, byte1=0x12 -> raw 12 bits starting at bit 4 = 0x120
+        let frame = CanFrame {
+            id: 0x701,
+            extended: false,
+            fd: false,
+            remote: false,
+            data: vec![0x0F, 0x12, 0, 0, 0, 0, 0, 0],
+            header: None,
+        };
+
+        let signals = decode(&frame, &[def]);
+
+        assert_eq!(signals["straddling"].value, serde_json::json!(0x120 as f64));
+    }
+
+    #[test]
+    fn built_in_defs_cover_vehicle_status_and_all_radar_ids() {
+        let defs = built_in_defs();
+        assert_eq!(defs.iter().filter(|d| d.can_id == 0x123).count(), 3);
+        for radar_id in [0x521u32, 0x522, 0x523, 0x524] {
+            assert_eq!(defs.iter().filter(|d| d.can_id == radar_id).count(), 1);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! slcan（LAWICEL ASCII）编码/解码
+//!
+//! 这个 crate 目前只会讲适配器自己的 0xAA/0x55 二进制协议（见 `can_protocol.rs`/
+//! `can_frame.rs`），把抓到的帧锁在这个 app 里。slcan 是 LAWICEL CANUSB 那一代
+//! 串口 CAN 适配器定下来的纯文本行协议，socketcan（`slcand`）之类的工具链到现在
+//! 还在沿用，这里转换的是 [`CanFrame`] <-> 这种 ASCII 行，方便跟外部工具互通日志，
+//! 不涉及真的打开一路 slcan 串口设备。
+//!
+//! 行格式（每行以 `\r` 结尾，这里解析/生成都不处理波特率设置等其它 LAWICEL 命令）：
+//! - `t` + 3 位十六进制标准帧 ID + 1 位 DLC + 2*DLC 位十六进制数据：标准数据帧
+//! - `T` + 8 位十六进制扩展帧 ID + 1 位 DLC + 2*DLC 位十六进制数据：扩展数据帧
+//! - `r` + 3 位十六进制标准帧 ID + 1 位 DLC（无数据）：标准远程帧
+//! - `R` + 8 位十六进制扩展帧 ID + 1 位 DLC（无数据）：扩展远程帧
+
+use std::fmt;
+
+use crate::can_frame::CanFrame;
+
+/// [`from_slcan`] 能失败的具体原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// 空行，或者压根没有以 `t`/`T`/`r`/`R` 开头
+    UnknownCommand,
+    /// ID 部分不是合法的十六进制，或者长度不对（标准帧3位/扩展帧8位）
+    InvalidId,
+    /// DLC 字符不是 0-8 的数字（slcan 的 DLC 只有 0-8，不支持 CAN FD 那几档）
+    InvalidDlc(char),
+    /// 数据部分长度和 DLC 声明的不一致，或者不是合法十六进制
+    InvalidData,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownCommand => write!(f, "unknown slcan command, expected t/T/r/R"),
+            Error::InvalidId => write!(f, "invalid slcan CAN ID"),
+            Error::InvalidDlc(c) => write!(f, "invalid slcan DLC digit: '{}'", c),
+            Error::InvalidData => write!(f, "invalid or mismatched slcan data bytes"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// 把 [`CanFrame`] 编码成一行 slcan ASCII（含结尾的 `\r`，不含 `\n`）
+///
+/// 远程帧（`remote == true`）按 LAWICEL 约定不带数据负载；`fd` 帧目前没有对应的
+/// slcan 命令字符，emit 时按普通数据帧处理，不携带 FD 信息（slcan 协议本身就没有
+/// 这个概念）
+pub fn to_slcan(frame: &CanFrame) -> String {
+    let command = match (frame.extended, frame.remote) {
+        (false, false) => 't',
+        (true, false) => 'T',
+        (false, true) => 'r',
+        (true, true) => 'R',
+    };
+
+    let id_digits = if frame.extended { 8 } else { 3 };
+    let dlc = frame.data.len().min(8);
+
+    let mut line = String::new();
+    line.push(command);
+    line.push_str(&format!("{:0width$X}", frame.id, width = id_digits));
+    line.push_str(&format!("{:X}", dlc));
+
+    if !frame.remote {
+        for byte in &frame.data {
+            line.push_str(&format!("{:02X}", byte));
+        }
+    }
+
+    line.push('\r');
+    line
+}
+
+/// 把一行 slcan ASCII（结尾的 `\r`/`\n` 可有可无）解析成 [`CanFrame`]
+pub fn from_slcan(line: &str) -> Result<CanFrame> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let mut chars = line.chars();
+    let command = chars.next().ok_or(Error::UnknownCommand)?;
+
+    let (extended, remote) = match command {
+        't' => (false, false),
+        'T' => (true, false),
+        'r' => (false, true),
+        'R' => (true, true),
+        _ => return Err(Error::UnknownCommand),
+    };
+
+    let id_digits = if extended { 8 } else { 3 };
+    let rest = chars.as_str();
+    if rest.len() < id_digits + 1 {
+        return Err(Error::InvalidId);
+    }
+
+    let (id_str, rest) = rest.split_at(id_digits);
+    let id = u32::from_str_radix(id_str, 16).map_err(|_| Error::InvalidId)?;
+
+    let mut rest_chars = rest.chars();
+    let dlc_char = rest_chars.next().ok_or(Error::InvalidDlc(' '))?;
+    let dlc = dlc_char.to_digit(16).ok_or(Error::InvalidDlc(dlc_char))? as usize;
+    if dlc > 8 {
+        return Err(Error::InvalidDlc(dlc_char));
+    }
+
+    let data = if remote {
+        Vec::new()
+    } else {
+        let data_str = rest_chars.as_str();
+        if data_str.len() != dlc * 2 {
+            return Err(Error::InvalidData);
+        }
+        data_str
+            .as_bytes()
+            .chunks(2)
+            .map(|chunk| {
+                let hex_str = std::str::from_utf8(chunk).map_err(|_| Error::InvalidData)?;
+                u8::from_str_radix(hex_str, 16).map_err(|_| Error::InvalidData)
+            })
+            .collect::<Result<Vec<u8>>>()?
+    };
+
+    Ok(CanFrame { id, extended, fd: false, remote, data, header: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_standard_data_frame() {
+        let frame = CanFrame {
+            id: 0x123,
+            extended: false,
+            fd: false,
+            remote: false,
+            data: vec![0x11, 0x22, 0x33, 0x44],
+            header: None,
+        };
+        assert_eq!(to_slcan(&frame), "t123411223344\r");
+    }
+
+    #[test]
+    fn encodes_extended_data_frame() {
+        let frame = CanFrame {
+            id: 0x1ABCDEF0,
+            extended: true,
+            fd: false,
+            remote: false,
+            data: vec![0xDE, 0xAD],
+            header: None,
+        };
+        assert_eq!(to_slcan(&frame), "T1ABCDEF02DEAD\r");
+    }
+
+    #[test]
+    fn encodes_remote_frame_without_data() {
+        let frame = CanFrame {
+            id: 0x7FF,
+            extended: false,
+            fd: false,
+            remote: true,
+            data: vec![],
+            header: None,
+        };
+        assert_eq!(to_slcan(&frame), "r7FF0\r");
+    }
+
+    #[test]
+    fn decodes_standard_data_frame() {
+        let frame = from_slcan("t123411223344\r").unwrap();
+        assert_eq!(frame.id, 0x123);
+        assert!(!frame.extended);
+        assert!(!frame.remote);
+        assert_eq!(frame.data, vec![0x11, 0x22, 0x33, 0x44]);
+    }
+
+    #[test]
+    fn decodes_extended_remote_frame() {
+        let frame = from_slcan("R1ABCDEF02\r").unwrap();
+        assert_eq!(frame.id, 0x1ABCDEF0);
+        assert!(frame.extended);
+        assert!(frame.remote);
+        assert!(frame.data.is_empty());
+    }
+
+    #[test]
+    fn round_trips_through_encode_and_decode() {
+        let frame = CanFrame {
+            id: 0x18C4D2D0,
+            extended: true,
+            fd: false,
+            remote: false,
+            data: vec![0x01, 0x83, 0x02, 0x02, 0xF2, 0x00, 0x00, 0x00],
+            header: None,
+        };
+        let line = to_slcan(&frame);
+        let decoded = from_slcan(&line).unwrap();
+        assert_eq!(decoded, frame);
+    }
+
+    #[test]
+    fn rejects_unknown_command_char() {
+        assert_eq!(from_slcan("x1238AABBCCDD\r").unwrap_err(), Error::UnknownCommand);
+    }
+
+    #[test]
+    fn rejects_data_length_mismatched_with_dlc() {
+        assert_eq!(from_slcan("t123411\r").unwrap_err(), Error::InvalidData);
+    }
+
+    #[test]
+    fn rejects_invalid_hex_id() {
+        assert_eq!(from_slcan("tZZZ0\r").unwrap_err(), Error::InvalidId);
+    }
+}
@@ -1,67 +1,70 @@
 use std::sync::atomic::Ordering;
-use std::thread;
-use std::time::Duration;
+use std::sync::mpsc;
 
-use log::{error, info};
+use log::info;
 use serialport::SerialPort;
 use tauri::Emitter;
 
-use crate::AppState;
+use crate::reactor::Reactor;
+use crate::ring_buffer::RingBuffer;
+use crate::{AppState, SendMessage};
 
-/// Start System Monitor Thread
+/// 消息缓冲区容量，足够容纳多个 18 字节帧
+const MESSAGE_BUFFER_CAPACITY: usize = 1024;
+
+/// 将系统监控串口注册到共享的 [`Reactor`] 事件循环上，返回注册得到的端口 id
+///
+/// 系统监控目前是只读的，这里给它一个空的写请求通道占位，保持 Reactor 注册接口统一。
 pub fn start_system_monitor_thread(
-    mut serial_port: Box<dyn SerialPort>,
+    serial_port: Box<dyn SerialPort>,
     state: AppState,
     app_handle: tauri::AppHandle,
-) {
+) -> u64 {
     state
         .system_monitor_thread_running
         .store(true, Ordering::SeqCst);
 
-    thread::spawn(move || {
-        let mut buffer = vec![0u8; 1024];
-        let mut message_buffer = Vec::new();
-
-        info!("🚀 [SystemMonitor Thread] Started");
-
-        while state.system_monitor_thread_running.load(Ordering::SeqCst) {
-            match serial_port.read(&mut buffer) {
-                Ok(n) if n > 0 => {
-                    let received_data = &buffer[..n];
-                    message_buffer.extend_from_slice(received_data);
-                    process_system_monitor_buffer(&mut message_buffer, &app_handle);
-                }
-                Ok(_) => {
-                    thread::sleep(Duration::from_millis(5));
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                    continue;
-                }
-                Err(e) => {
-                    error!("SystemMonitor thread: read error: {}", e);
-                    thread::sleep(Duration::from_millis(10));
-                }
-            }
+    let (_tx, rx) = mpsc::channel::<SendMessage>();
+    let mut message_buffer = RingBuffer::with_capacity(MESSAGE_BUFFER_CAPACITY);
+    let handler = Box::new(move |data: &[u8]| {
+        let enqueued = message_buffer.enqueue(data);
+        if enqueued < data.len() {
+            log::warn!(
+                "SystemMonitor: message buffer full, dropped {} bytes",
+                data.len() - enqueued
+            );
         }
-
-        info!("SystemMonitor thread stopped");
+        process_system_monitor_buffer(&mut message_buffer, &app_handle);
     });
+
+    let port_id = Reactor::global().add_port(serial_port, handler, rx);
+    info!(
+        "SystemMonitor: port registered with reactor as port {}",
+        port_id
+    );
+    port_id
 }
 
-fn process_system_monitor_buffer(message_buffer: &mut Vec<u8>, app_handle: &tauri::AppHandle) {
+fn process_system_monitor_buffer(message_buffer: &mut RingBuffer, app_handle: &tauri::AppHandle) {
+    let mut scratch = [0u8; 18];
+
     loop {
         // Find header 0xAA 0x55
-        let header_pos = message_buffer.windows(2).position(|w| w == [0xAA, 0x55]);
+        let header_pos = message_buffer.find(&[0xAA, 0x55]);
 
         if let Some(pos) = header_pos {
             // Discard data before header
             if pos > 0 {
-                message_buffer.drain(0..pos);
+                message_buffer.advance(pos);
             }
 
             // Check if we have enough bytes (18 bytes total)
             if message_buffer.len() >= 18 {
-                let packet: Vec<u8> = message_buffer.drain(0..18).collect();
+                let packet = message_buffer
+                    .peek(18, &mut scratch)
+                    .expect("len checked above")
+                    .to_vec();
+                message_buffer.advance(18);
 
                 // Emit event
                 let _ = app_handle.emit("system-monitor-data", packet);
@@ -71,16 +74,14 @@ fn process_system_monitor_buffer(message_buffer: &mut Vec<u8>, app_handle: &taur
             }
         } else {
             // No header found, keep last byte just in case it's 0xAA
-            if message_buffer.len() > 1 {
-                let keep_last = if message_buffer.last() == Some(&0xAA) {
+            let len = message_buffer.len();
+            if len > 1 {
+                let keep_last = if message_buffer.peek_byte(len - 1) == Some(0xAA) {
                     1
                 } else {
                     0
                 };
-                let len = message_buffer.len();
-                if len > keep_last {
-                    message_buffer.drain(0..len - keep_last);
-                }
+                message_buffer.advance(len - keep_last);
             }
             break;
         }
@@ -0,0 +1,157 @@
+//! 有界发送队列
+//!
+//! `tx_send` 原来是一个没有上限的 `mpsc::channel`：CSV 循环、MQTT 桥接这类生产者
+//! 吐包的速度可能远超 CAN 链路本身（通常只有几十到几百 kbps），旧实现会在内存里
+//! 无限堆积待发送的包，且完全没有可观测性。这里换成 `mpsc::sync_channel` 包一层
+//! 显式的 `QueueFull` 错误和计数器，生产者必须自己决定满了之后是报错还是退避重试。
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serde::Serialize;
+use tauri::Emitter;
+
+use crate::SendMessage;
+
+/// 队列已满时无法继续入队
+#[derive(Debug)]
+pub struct QueueFull;
+
+impl std::fmt::Display for QueueFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tx queue is full")
+    }
+}
+
+impl std::error::Error for QueueFull {}
+
+/// 供 `get_tx_queue_stats` 返回给前端的队列快照
+#[derive(Debug, Clone, Serialize)]
+pub struct TxQueueStats {
+    pub depth: u64,
+    pub capacity: usize,
+    pub total_enqueued: u64,
+    pub total_dropped: u64,
+}
+
+/// 队列持续处于满载状态超过这个时长才发一次 "tx-queue-saturated"，避免瞬时尖峰刷屏
+const SATURATION_THRESHOLD: Duration = Duration::from_secs(2);
+const SATURATION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 有界发送队列的生产者句柄，可以自由 clone 后分发给多个线程（CSV 循环、MQTT 桥接等）
+#[derive(Clone)]
+pub struct TxQueue {
+    sender: SyncSender<SendMessage>,
+    capacity: usize,
+    depth: Arc<AtomicU64>,
+    total_enqueued: Arc<AtomicU64>,
+    total_dropped: Arc<AtomicU64>,
+}
+
+impl TxQueue {
+    /// 创建一个容量为 `capacity` 的有界发送队列；返回生产者句柄、消费端 `Receiver`
+    /// （类型和原来的 `mpsc::channel` 一样，Reactor/系统监控等消费侧代码不用改），
+    /// 以及供消费侧在每次成功取走一个包后递减的深度计数器
+    pub fn bounded(capacity: usize) -> (Self, Receiver<SendMessage>, Arc<AtomicU64>) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let depth = Arc::new(AtomicU64::new(0));
+        (
+            Self {
+                sender,
+                capacity,
+                depth: depth.clone(),
+                total_enqueued: Arc::new(AtomicU64::new(0)),
+                total_dropped: Arc::new(AtomicU64::new(0)),
+            },
+            receiver,
+            depth,
+        )
+    }
+
+    /// 立即尝试入队；队列已满时不阻塞，直接返回 `QueueFull`（并计入 dropped 计数）
+    pub fn try_enqueue(&self, msg: SendMessage) -> Result<(), QueueFull> {
+        match self.sender.try_send(msg) {
+            Ok(()) => {
+                self.depth.fetch_add(1, Ordering::SeqCst);
+                self.total_enqueued.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+            Err(_) => {
+                self.total_dropped.fetch_add(1, Ordering::SeqCst);
+                Err(QueueFull)
+            }
+        }
+    }
+
+    /// 队列满时退避重试，直到成功或 `timeout` 用尽；给后台循环（CSV 回放、`safety.rs`
+    /// 的停止帧）一个比"立刻报错"更宽容的选项，容忍偶发的瞬时拥塞
+    pub fn enqueue_blocking(&self, mut msg: SendMessage, timeout: Duration) -> Result<(), QueueFull> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.sender.try_send(msg) {
+                Ok(()) => {
+                    self.depth.fetch_add(1, Ordering::SeqCst);
+                    self.total_enqueued.fetch_add(1, Ordering::SeqCst);
+                    return Ok(());
+                }
+                Err(TrySendError::Full(returned)) => {
+                    if Instant::now() >= deadline {
+                        self.total_dropped.fetch_add(1, Ordering::SeqCst);
+                        return Err(QueueFull);
+                    }
+                    msg = returned;
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    self.total_dropped.fetch_add(1, Ordering::SeqCst);
+                    return Err(QueueFull);
+                }
+            }
+        }
+    }
+
+    /// 当前队列深度/容量/累计入队/累计丢弃的快照
+    pub fn stats(&self) -> TxQueueStats {
+        TxQueueStats {
+            depth: self.depth.load(Ordering::SeqCst),
+            capacity: self.capacity,
+            total_enqueued: self.total_enqueued.load(Ordering::SeqCst),
+            total_dropped: self.total_dropped.load(Ordering::SeqCst),
+        }
+    }
+
+    /// 启动一个后台线程：持续满载超过 `SATURATION_THRESHOLD` 就发一次
+    /// `"tx-queue-saturated"` 事件；`running` 复用这次连接的写线程运行标志，
+    /// 和连接本身的生命周期保持一致，断开连接时自然停掉
+    pub fn start_saturation_watcher(&self, app_handle: tauri::AppHandle, running: Arc<AtomicBool>) {
+        let queue = self.clone();
+        thread::spawn(move || {
+            let mut full_since: Option<Instant> = None;
+            let mut already_emitted = false;
+
+            while running.load(Ordering::SeqCst) {
+                thread::sleep(SATURATION_POLL_INTERVAL);
+
+                let stats = queue.stats();
+                if stats.depth >= stats.capacity as u64 {
+                    let since = *full_since.get_or_insert_with(Instant::now);
+                    if !already_emitted && since.elapsed() > SATURATION_THRESHOLD {
+                        warn!(
+                            "TxQueue: saturated for over {:?} ({:?})",
+                            SATURATION_THRESHOLD, stats
+                        );
+                        let _ = app_handle.emit("tx-queue-saturated", &stats);
+                        already_emitted = true;
+                    }
+                } else {
+                    full_since = None;
+                    already_emitted = false;
+                }
+            }
+        });
+    }
+}